@@ -0,0 +1,121 @@
+//! Hooks for the programs playing the game, to train or benchmark them without a terminal.
+//!
+//! An [`Agent`] is handed a [`BoardView`] of the game each time it can move the current piece, and
+//! answers with the action to apply. A [`Runner`] plays a whole game that way, stepping the gravity
+//! as fast as wanted rather than in real time. The [`Bot`](crate::bot::Bot) is an agent.
+
+use std::time::Duration;
+
+use crate::{Mino, Tetromino, TetrominoSequence, Ttrys, UserAction};
+
+/// A player choosing its actions from a snapshot of the game
+pub trait Agent {
+    /// The action to apply to the current piece. Only the actions moving, rotating, dropping or
+    /// holding the piece are applied by the [`Runner`].
+    fn act(&mut self, board: &BoardView) -> UserAction;
+}
+
+/// What a player sees of a game at a given time
+#[derive(Clone, Debug)]
+pub struct BoardView {
+    /// Number of columns of the stack
+    pub width: usize,
+    /// Number of visible rows of the stack
+    pub height: usize,
+    /// The visible rows of `width` cells, from the bottom up, without the current piece
+    pub stack: Vec<Mino>,
+    /// Rows up to the highest occupied cell of each column, see [`Ttrys::column_heights`]
+    pub column_heights: Vec<u8>,
+    /// The current piece and the (column, row) positions of its minos, see [`Ttrys::piece`]
+    pub piece: Option<(Tetromino, [(i8, i8); 4])>,
+    /// The next [`TetrominoSequence::MAX_PREVIEW`] pieces
+    pub queue: Vec<Tetromino>,
+    /// The held piece
+    pub hold: Option<Tetromino>,
+    /// Whether the held piece was already swapped since the last spawn
+    pub hold_used: bool,
+    /// Number of locked pieces, see [`Statistics::pieces`](crate::Statistics::pieces)
+    pub pieces: u32,
+}
+
+impl BoardView {
+    pub fn new(ttrys: &Ttrys) -> Self {
+        BoardView {
+            width: ttrys.width,
+            height: ttrys.height,
+            stack: ttrys.stack[..ttrys.width * ttrys.height].to_vec(),
+            column_heights: ttrys.column_heights.clone(),
+            piece: ttrys.piece(),
+            queue: ttrys.upcoming(TetrominoSequence::MAX_PREVIEW),
+            hold: ttrys.hold,
+            hold_used: ttrys.hold_used,
+            pieces: ttrys.stats().pieces(),
+        }
+    }
+
+    /// Content of the stack at the given column and row, row 0 being the bottom one
+    pub fn mino(&self, col: usize, row: usize) -> Mino {
+        self.stack[row * self.width + col]
+    }
+}
+
+/// Plays games with an [`Agent`], without waiting for the gravity
+#[derive(Clone, Debug)]
+pub struct Runner {
+    /// Actions the agent may take between two steps of the gravity, a slow agent lets the pieces
+    /// fall before reaching their place
+    pub actions_per_step: u32,
+    /// Time to wait after each step, zero to play as fast as possible
+    pub step_interval: Duration,
+    /// End the game after this many pieces locked, if any
+    pub max_pieces: Option<u32>,
+}
+
+impl Default for Runner {
+    fn default() -> Self {
+        Runner {
+            actions_per_step: 10,
+            step_interval: Duration::ZERO,
+            max_pieces: None,
+        }
+    }
+}
+
+impl Runner {
+    /// Play a game to its end, the score and the statistics are then read from the game
+    pub fn run(&self, ttrys: &mut Ttrys, agent: &mut dyn Agent) {
+        while ttrys.running() {
+            if self
+                .max_pieces
+                .is_some_and(|max_pieces| ttrys.stats().pieces() >= max_pieces)
+            {
+                ttrys.update(UserAction::Quit);
+                break;
+            }
+            for _ in 0..self.actions_per_step {
+                if !ttrys.controllable() {
+                    break;
+                }
+                let action = agent.act(&BoardView::new(ttrys));
+                let playing = matches!(
+                    action,
+                    UserAction::MoveLeft
+                        | UserAction::MoveRight
+                        | UserAction::RotateCW
+                        | UserAction::RotateCCW
+                        | UserAction::Rotate180
+                        | UserAction::HardDrop
+                        | UserAction::SoftDrop
+                        | UserAction::Hold
+                );
+                if playing {
+                    ttrys.update(action);
+                }
+            }
+            ttrys.step();
+            if !self.step_interval.is_zero() {
+                std::thread::sleep(self.step_interval);
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// Sound effects. With the "audio" feature, they are synthesized and played by a thread of their
+// own; without it, or without a sound device, the game stays silent.
+
+use ttrys::GameEvent;
+
+pub struct Audio {
+    #[cfg(feature = "audio")]
+    sender: Option<std::sync::mpsc::Sender<GameEvent>>,
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Audio {
+            #[cfg(feature = "audio")]
+            sender: backend::spawn(),
+        }
+    }
+
+    pub fn play(&self, events: &[GameEvent]) {
+        #[cfg(feature = "audio")]
+        if let Some(sender) = &self.sender {
+            for &event in events {
+                sender.send(event).ok();
+            }
+        }
+        #[cfg(not(feature = "audio"))]
+        let _ = events;
+    }
+}
+
+#[cfg(feature = "audio")]
+mod backend {
+    use std::sync::mpsc::{self, Sender};
+    use std::time::Duration;
+
+    use rodio::source::{SineWave, Source};
+    use rodio::{OutputStream, OutputStreamHandle};
+
+    use ttrys::{ClearKind, GameEvent};
+
+    const VOLUME: f32 = 0.2;
+
+    // The output stream can't leave the thread opening it, the sounds are sent over to it. None if
+    // there is no sound device.
+    pub fn spawn() -> Option<Sender<GameEvent>> {
+        let (sender, receiver) = mpsc::channel();
+        let (opened, open) = mpsc::channel();
+        std::thread::spawn(move || {
+            let Ok((_stream, handle)) = OutputStream::try_default() else {
+                opened.send(false).ok();
+                return;
+            };
+            opened.send(true).ok();
+            for event in receiver {
+                play(&handle, event);
+            }
+        });
+        open.recv().unwrap_or(false).then_some(sender)
+    }
+
+    // The notes of the sound of an event, frequency (Hz) and length (ms), one after the other
+    fn notes(event: GameEvent) -> &'static [(f32, u64)] {
+        match event {
+            GameEvent::PieceMoved => &[(440.0, 15)],
+            GameEvent::PieceRotated => &[(660.0, 20)],
+            GameEvent::PieceLocked { .. } => &[(220.0, 40)],
+            GameEvent::LinesCleared {
+                kind: ClearKind::Tetris,
+                ..
+            } => &[(523.3, 60), (659.3, 60), (784.0, 60), (1046.5, 120)],
+            GameEvent::LinesCleared { .. } => &[(523.3, 60), (659.3, 80)],
+            GameEvent::LevelUp { .. } => &[(784.0, 80), (1046.5, 120)],
+            GameEvent::GameOver { .. } => &[(392.0, 150), (311.1, 150), (261.6, 300)],
+            _ => &[],
+        }
+    }
+
+    fn play(handle: &OutputStreamHandle, event: GameEvent) {
+        let mut start = Duration::ZERO;
+        for &(frequency, length) in notes(event) {
+            let length = Duration::from_millis(length);
+            let note = SineWave::new(frequency)
+                .take_duration(length)
+                .amplify(VOLUME)
+                .delay(start);
+            handle.play_raw(note).ok();
+            start += length;
+        }
+    }
+}
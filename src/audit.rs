@@ -0,0 +1,103 @@
+// Headless randomizer audit: `ttrys audit-rng --samples N --randomizer <name>`
+//
+// Draws N pieces from the chosen randomizer and prints the piece distribution, a chi-square
+// goodness of fit against the uniform distribution, and a histogram of drought lengths.
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use ttrys::{DroughtTracker, RandomizerKind, Tetromino, TetrominoSequence};
+
+const USAGE: &str =
+    "usage: ttrys audit-rng [--samples N] [--randomizer seven-bag|bag-<1..7>|classic|tgm]";
+
+// Critical values of the chi-square distribution with 6 degrees of freedom
+const CHI2_CRITICAL_5: f64 = 12.592;
+const CHI2_CRITICAL_1: f64 = 16.812;
+
+// Droughts of this length and longer are gathered in the last histogram bucket
+const HISTOGRAM_BUCKETS: usize = 25;
+
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut samples = 100_000;
+    let mut randomizer_name = String::from("seven-bag");
+    while let Some(arg) = args.next() {
+        let value = args.next();
+        match (arg.as_str(), value) {
+            ("--samples", Some(value)) => {
+                samples = value
+                    .parse()
+                    .map_err(|_| format!("invalid sample count '{}'\n{}", value, USAGE))?
+            }
+            ("--randomizer", Some(value)) => randomizer_name = value,
+            _ => return Err(format!("unexpected argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+    if samples == 0 {
+        return Err(format!("the sample count must be positive\n{}", USAGE));
+    }
+    // the names used before the game had randomizers of its own
+    let kind = match randomizer_name.as_str() {
+        "bag" => Some(RandomizerKind::Bag(5)),
+        "uniform" => Some(RandomizerKind::Classic),
+        name => RandomizerKind::from_name(name),
+    }
+    .ok_or_else(|| format!("unknown randomizer '{}'\n{}", randomizer_name, USAGE))?;
+    let mut rng = StdRng::from_entropy();
+    let mut sequence = TetrominoSequence::new(kind, &mut rng);
+
+    let mut counts = [0u64; 7];
+    let mut droughts = DroughtTracker::default();
+    let mut histogram = [0u64; HISTOGRAM_BUCKETS];
+    for _ in 0..samples {
+        let tetro = sequence.pop(&mut rng);
+        // the drought that just ended is the one of the piece being dealt
+        if counts[tetro as usize] > 0 {
+            let drought = droughts.current(tetro) as usize;
+            histogram[drought.min(HISTOGRAM_BUCKETS - 1)] += 1;
+        }
+        counts[tetro as usize] += 1;
+        droughts.record(tetro);
+    }
+
+    println!("Randomizer: {}, {} samples", randomizer_name, samples);
+    println!();
+    println!("Distribution:");
+    let expected = samples as f64 / 7.0;
+    let mut chi2 = 0.0;
+    for (idx, &count) in counts.iter().enumerate() {
+        let tetro: Tetromino = idx.into();
+        let share = 100.0 * count as f64 / samples as f64;
+        println!("  {}  {:>10}  {:>6.2}%", tetro, count, share);
+        chi2 += (count as f64 - expected).powi(2) / expected;
+    }
+    println!();
+    let verdict = if chi2 > CHI2_CRITICAL_1 {
+        "not uniform (p < 0.01)"
+    } else if chi2 > CHI2_CRITICAL_5 {
+        "likely not uniform (p < 0.05)"
+    } else {
+        "consistent with uniform"
+    };
+    println!("Chi-square: {:.3} (6 dof), {}", chi2, verdict);
+    println!();
+
+    println!("Droughts (pieces between two occurrences of the same tetromino):");
+    let max = histogram.iter().copied().max().unwrap_or(0).max(1);
+    for (length, &count) in histogram.iter().enumerate() {
+        let label = if length == HISTOGRAM_BUCKETS - 1 {
+            format!("{}+", length)
+        } else {
+            format!("{}", length)
+        };
+        let bar = "#".repeat((50 * count / max) as usize);
+        println!("  {:>3}  {:>10}  {}", label, count, bar);
+    }
+    println!();
+    println!("Longest droughts:");
+    for idx in 0..7 {
+        let tetro: Tetromino = idx.into();
+        println!("  {}  {}", tetro, droughts.longest(tetro));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,190 @@
+//! A player for demos and benchmarks, going through the same [`Ttrys::update`] calls as a human.
+//!
+//! Each piece goes to the landing position rated best by a weighted sum of the aggregate height,
+//! holes and bumpiness of the resulting stack, and of the lines it clears. The pieces are dropped
+//! straight down: the bot does not slide them under overhangs, nor use the hold.
+
+use crate::agent::{Agent, BoardView};
+use crate::{Mino, Ttrys, UserAction};
+
+// weights of the evaluation, as tuned by a genetic search (Yiyuan Lee, 2013)
+const HEIGHT_WEIGHT: f32 = -0.510066;
+const LINES_WEIGHT: f32 = 0.760666;
+const HOLES_WEIGHT: f32 = -0.35663;
+const BUMPINESS_WEIGHT: f32 = -0.184483;
+
+/// Plays one game, feed [`Bot::next_action`] to [`Ttrys::update`] at the pace of your liking
+#[derive(Default)]
+pub struct Bot {
+    // locked pieces and use of the hold when the target was planned, a new piece changes them
+    planned_for: Option<(u32, bool)>,
+    // placement aimed at for the current piece
+    target: Option<Target>,
+    // clockwise rotations done so far for the current piece
+    rotations: u8,
+    // column of the piece before the last move, to notice a blocked one
+    last_column: Option<i8>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Target {
+    // clockwise rotations from the state the piece was first seen in
+    rotations: u8,
+    // leftmost column of the piece
+    column: i8,
+}
+
+impl Bot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The next action taking the current piece to its place, None when there is no piece to
+    /// control
+    pub fn next_action(&mut self, ttrys: &Ttrys) -> Option<UserAction> {
+        ttrys
+            .controllable()
+            .then(|| self.act(&BoardView::new(ttrys)))
+    }
+}
+
+impl Agent for Bot {
+    fn act(&mut self, board: &BoardView) -> UserAction {
+        let Some((_, minos)) = board.piece else {
+            return UserAction::HardDrop;
+        };
+        // the piece locked by the gravity, or swapped with the held one, is a new one to plan for
+        let piece = (board.pieces, board.hold_used);
+        if self.planned_for != Some(piece) {
+            *self = Self {
+                planned_for: Some(piece),
+                ..Self::default()
+            };
+        }
+        let target = *self.target.get_or_insert_with(|| {
+            plan(board, minos).unwrap_or(Target {
+                rotations: 0,
+                column: 0,
+            })
+        });
+        if self.rotations < target.rotations {
+            self.rotations += 1;
+            return UserAction::RotateCW;
+        }
+
+        // a move that did not change the column ran into something, drop from there
+        let column = minos.iter().map(|mino| mino.0).min().unwrap_or(0);
+        let blocked = self.last_column == Some(column);
+        let action = match column.cmp(&target.column) {
+            _ if blocked => UserAction::HardDrop,
+            std::cmp::Ordering::Greater => UserAction::MoveLeft,
+            std::cmp::Ordering::Less => UserAction::MoveRight,
+            std::cmp::Ordering::Equal => UserAction::HardDrop,
+        };
+        self.last_column = Some(column);
+        action
+    }
+}
+
+// The best placement of the current piece, as first seen, on the stack, None if it fits nowhere
+fn plan(board: &BoardView, minos: [(i8, i8); 4]) -> Option<Target> {
+    let (width, height) = (board.width, board.height);
+    let stack: Vec<bool> = board.stack.iter().map(|&mino| mino != Mino::Free).collect();
+
+    let mut shape = normalize(minos);
+    let mut shapes: Vec<[(i8, i8); 4]> = Vec::new();
+    let mut best: Option<(f32, Target)> = None;
+    for rotations in 0..4 {
+        // the symmetric pieces look the same after some rotations
+        if !shapes.contains(&shape) {
+            shapes.push(shape);
+            let shape_width = shape.iter().map(|mino| mino.0).max().unwrap_or(0) as usize + 1;
+            for column in 0..=width.saturating_sub(shape_width) {
+                let Some(placed) = drop_shape(&stack, width, height, &shape, column) else {
+                    continue;
+                };
+                let score = evaluate(&placed, width, height);
+                if best.is_none_or(|(best, _)| score > best) {
+                    let target = Target {
+                        rotations,
+                        column: column as i8,
+                    };
+                    best = Some((score, target));
+                }
+            }
+        }
+        // clockwise, with y pointing up
+        shape = normalize(shape.map(|(x, y)| (y, -x)));
+    }
+    best.map(|(_, target)| target)
+}
+
+// Minos moved against the bottom left corner, sorted so that equal shapes compare equal
+fn normalize(minos: [(i8, i8); 4]) -> [(i8, i8); 4] {
+    let left = minos.iter().map(|mino| mino.0).min().unwrap_or(0);
+    let bottom = minos.iter().map(|mino| mino.1).min().unwrap_or(0);
+    let mut minos = minos.map(|(x, y)| (x - left, y - bottom));
+    minos.sort();
+    minos
+}
+
+// The stack with the shape dropped from the top in the given column, None if it does not fit
+fn drop_shape(
+    stack: &[bool],
+    width: usize,
+    height: usize,
+    shape: &[(i8, i8); 4],
+    column: usize,
+) -> Option<Vec<bool>> {
+    let fits = |bottom: i32| {
+        shape.iter().all(|&(x, y)| {
+            let row = bottom + y as i32;
+            row >= 0
+                && (row as usize) < height
+                && !stack[row as usize * width + column + x as usize]
+        })
+    };
+    let top = height as i32 - 1 - shape.iter().map(|mino| mino.1).max().unwrap_or(0) as i32;
+    let mut bottom = top;
+    if !fits(bottom) {
+        return None;
+    }
+    while fits(bottom - 1) {
+        bottom -= 1;
+    }
+    let mut placed = stack.to_vec();
+    for &(x, y) in shape {
+        placed[(bottom + y as i32) as usize * width + column + x as usize] = true;
+    }
+    Some(placed)
+}
+
+// Rating of a stack, the higher the better. Full rows count as cleared.
+fn evaluate(stack: &[bool], width: usize, height: usize) -> f32 {
+    let rows: Vec<&[bool]> = stack
+        .chunks(width)
+        .filter(|row| !row.iter().all(|&filled| filled))
+        .collect();
+    let lines = height - rows.len();
+
+    let mut heights = vec![0; width];
+    let mut holes = 0;
+    for (col, column_height) in heights.iter_mut().enumerate() {
+        for (row, cells) in rows.iter().enumerate() {
+            if cells[col] {
+                *column_height = row + 1;
+            }
+        }
+        holes += (0..*column_height).filter(|&row| !rows[row][col]).count();
+    }
+    let aggregate: usize = heights.iter().sum();
+    let bumpiness: usize = heights
+        .windows(2)
+        .map(|pair| pair[0].abs_diff(pair[1]))
+        .sum();
+
+    HEIGHT_WEIGHT * aggregate as f32
+        + LINES_WEIGHT * lines as f32
+        + HOLES_WEIGHT * holes as f32
+        + BUMPINESS_WEIGHT * bumpiness as f32
+}
@@ -0,0 +1,143 @@
+// Settings bundle: `ttrys config export [file]` and `ttrys config import <file>`
+//
+// The bundle gathers the settings files in one text file, to move them to another machine or keep
+// them with dotfiles. Each file is a section starting with its name in brackets:
+//
+//     # ttrys settings bundle 1
+//     [config]
+//     color.S = 34
+//
+// The configuration file is the only one for now; key bindings and themes are chosen on the
+// command line. Without a file name, export writes to the standard output and import reads from
+// the standard input.
+
+use std::io::Read;
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+const USAGE: &str = "usage: ttrys config export [file] | ttrys config import [file]";
+const HEADER: &str = "# ttrys settings bundle 1";
+
+// The settings files, by section name
+fn files() -> Result<Vec<(&'static str, PathBuf)>, String> {
+    let config =
+        Config::path().ok_or("cannot locate the configuration directory (HOME is not set)")?;
+    Ok(vec![("config", config)])
+}
+
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let command = args.next();
+    let file = args.next();
+    if let Some(arg) = args.next() {
+        return Err(format!("unexpected argument '{}'\n{}", arg, USAGE));
+    }
+    match command.as_deref() {
+        Some("export") => export(file),
+        Some("import") => import(file),
+        _ => Err(String::from(USAGE)),
+    }
+}
+
+fn export(file: Option<String>) -> Result<(), String> {
+    let mut bundle = format!("{}\n", HEADER);
+    for (name, path) in files()? {
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                bundle += &format!("[{}]\n{}", name, text);
+                if !text.is_empty() && !text.ends_with('\n') {
+                    bundle.push('\n');
+                }
+            }
+            // settings left to their defaults have no file, nor section
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(format!("{}: {}", path.display(), err)),
+        }
+    }
+    match file {
+        Some(file) => std::fs::write(&file, bundle).map_err(|err| format!("{}: {}", file, err)),
+        None => {
+            print!("{}", bundle);
+            Ok(())
+        }
+    }
+}
+
+fn import(file: Option<String>) -> Result<(), String> {
+    let (origin, bundle) = match file {
+        Some(file) => {
+            let text =
+                std::fs::read_to_string(&file).map_err(|err| format!("{}: {}", file, err))?;
+            (file, text)
+        }
+        None => {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|err| format!("standard input: {}", err))?;
+            (String::from("standard input"), text)
+        }
+    };
+
+    // split the sections, and check them all before touching any file
+    let files = files()?;
+    let mut sections: Vec<(usize, &PathBuf, String)> = Vec::new();
+    let mut lines = bundle.lines().enumerate();
+    if lines.next().map(|(_, line)| line.trim()) != Some(HEADER) {
+        return Err(format!("{}: not a ttrys settings bundle", origin));
+    }
+    for (line_number, line) in lines {
+        let section = line
+            .trim()
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'));
+        if let Some(name) = section {
+            let (_, path) = files
+                .iter()
+                .find(|(file_name, _)| *file_name == name)
+                .ok_or_else(|| {
+                    format!("{}:{}: unknown section '{}'", origin, line_number + 1, name)
+                })?;
+            if sections.iter().any(|(_, other, _)| *other == path) {
+                return Err(format!(
+                    "{}:{}: duplicate section '{}'",
+                    origin,
+                    line_number + 1,
+                    name
+                ));
+            }
+            sections.push((line_number + 1, path, String::new()));
+        } else if let Some((_, _, text)) = sections.last_mut() {
+            text.push_str(line);
+            text.push('\n');
+        } else if !line.trim().is_empty() && !line.trim().starts_with('#') {
+            return Err(format!(
+                "{}:{}: expected a section",
+                origin,
+                line_number + 1
+            ));
+        }
+    }
+    for (first_line, _, text) in &sections {
+        Config::parse(text).map_err(|err| {
+            // report the line in the bundle rather than in the section
+            let (line, message) = err.split_once(": ").unwrap_or(("0", &err));
+            let line = line.parse::<usize>().unwrap_or(0) + first_line;
+            format!("{}:{}: {}", origin, line, message)
+        })?;
+    }
+
+    // the files being replaced are kept aside, with a .bak extension
+    for (_, path, text) in &sections {
+        let error = |err: std::io::Error| format!("{}: {}", path.display(), err);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(error)?;
+        }
+        if path.exists() {
+            std::fs::copy(path, path.with_extension("bak")).map_err(error)?;
+        }
+        std::fs::write(path, text).map_err(error)?;
+        println!("Restored {}", path.display());
+    }
+    Ok(())
+}
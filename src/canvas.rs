@@ -0,0 +1,206 @@
+// Double-buffered rendering: frames are drawn into an in-memory grid of cells (the back buffer),
+// which is compared with the grid last sent to the terminal (the front buffer). Only the cells that
+// changed are sent, with as few cursor moves and style changes as possible.
+
+use std::fmt::Display;
+use std::io::Write;
+
+use crossterm::style::{self, Attribute, Color};
+use crossterm::terminal::{BeginSynchronizedUpdate, Clear, ClearType, EndSynchronizedUpdate};
+use crossterm::{cursor, QueueableCommand};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Pen {
+    fg: Color,
+    bg: Color,
+    bold: bool,
+    dim: bool,
+}
+
+impl Default for Pen {
+    fn default() -> Self {
+        Pen {
+            fg: Color::Reset,
+            bg: Color::Reset,
+            bold: false,
+            dim: false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Cell {
+    symbol: char,
+    pen: Pen,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            symbol: ' ',
+            pen: Pen::default(),
+        }
+    }
+}
+
+// A grid of cells drawn like a terminal: move the cursor, set the colors, print text. Anything
+// falling outside of the grid is dropped.
+#[derive(Clone, Default)]
+pub struct Canvas {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    cursor: (u16, u16),
+    pen: Pen,
+}
+
+impl Canvas {
+    fn new(width: u16, height: u16) -> Self {
+        Canvas {
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+            cursor: (0, 0),
+            pen: Pen::default(),
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    // Blank all the cells, to draw something else than the last frame over
+    pub fn clear(&mut self) {
+        self.cells.fill(Cell::default());
+    }
+
+    pub fn move_to(&mut self, col: u16, row: u16) {
+        self.cursor = (col, row);
+    }
+
+    pub fn set_foreground(&mut self, color: Color) {
+        self.pen.fg = color;
+    }
+
+    pub fn set_background(&mut self, color: Color) {
+        self.pen.bg = color;
+    }
+
+    pub fn reset_color(&mut self) {
+        self.pen.fg = Color::Reset;
+        self.pen.bg = Color::Reset;
+    }
+
+    // Only bold and dim text is supported, resetting the attributes resets the colors as well
+    pub fn set_attribute(&mut self, attribute: Attribute) {
+        match attribute {
+            Attribute::Bold => self.pen.bold = true,
+            Attribute::Dim => self.pen.dim = true,
+            Attribute::Reset => self.pen = Pen::default(),
+            _ => {}
+        }
+    }
+
+    pub fn print(&mut self, text: impl Display) {
+        self.print_symbols(text, false);
+    }
+
+    // Print over what is drawn already, the spaces letting it show through
+    pub fn print_over(&mut self, text: impl Display) {
+        self.print_symbols(text, true);
+    }
+
+    fn print_symbols(&mut self, text: impl Display, transparent: bool) {
+        let (mut col, row) = self.cursor;
+        for symbol in text.to_string().chars() {
+            if col < self.width && row < self.height && !(transparent && symbol == ' ') {
+                let index = row as usize * self.width as usize + col as usize;
+                self.cells[index] = Cell {
+                    symbol,
+                    pen: self.pen,
+                };
+            }
+            col = col.saturating_add(1);
+        }
+        self.cursor = (col, row);
+    }
+}
+
+#[derive(Default)]
+pub struct Screen {
+    // what the terminal shows
+    front: Canvas,
+    // the frame being drawn, kept from one frame to the next
+    back: Canvas,
+}
+
+impl Screen {
+    // Follow the size of the terminal. When it changes, the terminal is cleared and everything is
+    // sent again on the next frame.
+    pub fn fit(&mut self, out: &mut impl Write, width: u16, height: u16) -> crossterm::Result<()> {
+        if (width, height) != (self.back.width, self.back.height) {
+            out.queue(Clear(ClearType::All))?;
+            self.front = Canvas::new(width, height);
+            self.back = Canvas::new(width, height);
+        }
+        Ok(())
+    }
+
+    pub fn canvas(&mut self) -> &mut Canvas {
+        &mut self.back
+    }
+
+    // Send the cells changed since the last frame to the terminal
+    pub fn present(&mut self, out: &mut impl Write) -> crossterm::Result<()> {
+        out.queue(BeginSynchronizedUpdate)?;
+        // the terminal is left with the default style after each frame
+        let mut pen = Pen::default();
+        let mut position = None;
+        let width = self.back.width as usize;
+        let changes = self
+            .back
+            .cells
+            .iter()
+            .zip(&self.front.cells)
+            .enumerate()
+            .filter(|(_, (back, front))| back != front);
+        for (index, (cell, _)) in changes {
+            let (col, row) = ((index % width) as u16, (index / width) as u16);
+            if position != Some((col, row)) {
+                out.queue(cursor::MoveTo(col, row))?;
+            }
+            // attributes can only be turned off all at once, along with the colors
+            if (pen.bold && !cell.pen.bold) || (pen.dim && !cell.pen.dim) {
+                out.queue(style::SetAttribute(Attribute::Reset))?;
+                pen = Pen::default();
+            }
+            if cell.pen.bold && !pen.bold {
+                out.queue(style::SetAttribute(Attribute::Bold))?;
+            }
+            if cell.pen.dim && !pen.dim {
+                out.queue(style::SetAttribute(Attribute::Dim))?;
+            }
+            if cell.pen.fg != pen.fg {
+                out.queue(style::SetForegroundColor(cell.pen.fg))?;
+            }
+            if cell.pen.bg != pen.bg {
+                out.queue(style::SetBackgroundColor(cell.pen.bg))?;
+            }
+            out.queue(style::Print(cell.symbol))?;
+            pen = cell.pen;
+            position = Some((col + 1, row));
+        }
+        if pen != Pen::default() {
+            out.queue(style::SetAttribute(Attribute::Reset))?;
+        }
+        out.queue(EndSynchronizedUpdate)?;
+        out.flush()?;
+
+        self.front.clone_from(&self.back);
+        Ok(())
+    }
+}
@@ -0,0 +1,430 @@
+// User configuration, read from $XDG_CONFIG_HOME/ttrys/config (~/.config/ttrys/config)
+//
+// The file is a list of "key = value" lines, blank lines and lines starting with '#' are ignored:
+//
+//     # make S and Z easier to tell apart
+//     color.S = 34
+//     color.Z = #ff5f87
+//
+// Colors are given by name (see crossterm's Color), 256 colors index, or RGB value.
+//
+// The speed of the pieces along the levels can be picked for each mode, among the built-in curves
+// (nes, guideline, ttrys) or as the milliseconds per row at levels 0, 1, 2..., the last one
+// holding for the levels past it:
+//
+//     gravity.marathon = nes
+//     gravity.classic = 800, 700, 600, 500, 400, 300, 200, 100
+//
+// So can the scoring (ttrys, guideline, nes):
+//
+//     scoring.marathon = guideline
+//
+// The settings screen writes the other keys, editing the lines in place:
+//
+//     das = 170           delayed auto shift in ms, or "off" to leave the key repeat to the terminal
+//     arr = 30            auto repeat rate in ms
+//     are = gravity       spawn delay in ms, or "gravity" to wait for one step of the gravity
+//     mouse = off         click a column to move the piece there, the wheel rotates it
+//     ghost = on
+//     theme = auto        or a palette name, as for --theme
+//     preview = 3
+//     danger = 5          free rows left at the top of the stack when the danger warning starts
+//     sound = on
+//     style = double-space  or half-block, ascii
+
+use std::path::PathBuf;
+
+use crossterm::style::Color;
+
+use crate::theme::{parse_color, Palette};
+use crate::{RenderStyle, Tetromino};
+use ttrys::{GravityCurve, Mode, Scoring, TetrominoSequence};
+
+#[derive(Default)]
+pub struct Config {
+    // per tetromino color overrides
+    pub piece_colors: [Option<Color>; 7],
+    // per mode gravity curves, the modes not listed keep their default one
+    pub gravity: Vec<(Mode, GravityCurve)>,
+    // per mode scoring, the modes not listed keep the ttrys one
+    pub scoring: Vec<(Mode, Scoring)>,
+    pub settings: Settings,
+}
+
+// What the settings screen changes
+#[derive(Clone, PartialEq, Debug)]
+pub struct Settings {
+    // delay before a held move key repeats, None leaves the repeat to the terminal
+    pub das: Option<u16>,
+    // delay between the repeated moves, 0 moves to the wall at once
+    pub arr: u16,
+    // wait before the next piece spawns, None waits for one step of the gravity
+    pub spawn_delay: Option<u16>,
+    // the pieces can be played with the mouse
+    pub mouse: bool,
+    pub ghost: bool,
+    // None picks a palette depending on the terminal background
+    pub palette: Option<Palette>,
+    pub preview_count: usize,
+    // free rows at the top of the stack under which the danger warning kicks in
+    pub danger_margin: usize,
+    pub sound: bool,
+    pub style: RenderStyle,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            das: None,
+            arr: 30,
+            spawn_delay: None,
+            mouse: false,
+            ghost: true,
+            palette: None,
+            preview_count: 3,
+            danger_margin: 5,
+            sound: true,
+            style: RenderStyle::DoubleSpace,
+        }
+    }
+}
+
+impl Settings {
+    pub const MAX_DANGER_MARGIN: usize = 10;
+
+    // The lines of the configuration file, by key
+    fn entries(&self) -> [(&'static str, String); 10] {
+        let on_off = |on| String::from(if on { "on" } else { "off" });
+        [
+            (
+                "das",
+                self.das.map_or(String::from("off"), |das| das.to_string()),
+            ),
+            ("arr", self.arr.to_string()),
+            (
+                "are",
+                self.spawn_delay
+                    .map_or(String::from("gravity"), |delay| delay.to_string()),
+            ),
+            ("mouse", on_off(self.mouse)),
+            ("ghost", on_off(self.ghost)),
+            (
+                "theme",
+                String::from(self.palette.map_or("auto", Palette::name)),
+            ),
+            ("preview", self.preview_count.to_string()),
+            ("danger", self.danger_margin.to_string()),
+            ("sound", on_off(self.sound)),
+            ("style", String::from(self.style.name())),
+        ]
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join("ttrys").join("config"))
+    }
+
+    // A missing configuration file is not an error, the defaults are used instead
+    pub fn load() -> Result<Config, String> {
+        let Some(path) = Self::path() else {
+            return Ok(Config::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text).map_err(|err| format!("{}:{}", path.display(), err)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| format!("{}: {}", line_number + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| error(format!("expected 'key = value', got '{}'", line)))?;
+
+            if let Some(letter) = key.strip_prefix("color.") {
+                let tetro: Tetromino = "IJLOSTZ"
+                    .find(letter)
+                    .filter(|_| letter.len() == 1)
+                    .ok_or_else(|| error(format!("unknown tetromino '{}'", letter)))?
+                    .into();
+                let color = parse_color(value)
+                    .ok_or_else(|| error(format!("invalid color '{}'", value)))?;
+                config.piece_colors[tetro as usize] = Some(color);
+            } else if let Some(mode) = key.strip_prefix("gravity.") {
+                let mode = Mode::from_name(mode)
+                    .ok_or_else(|| error(format!("unknown mode '{}'", mode)))?;
+                let curve = parse_gravity(value)
+                    .ok_or_else(|| error(format!("invalid gravity curve '{}'", value)))?;
+                config.gravity.retain(|&(other, _)| other != mode);
+                config.gravity.push((mode, curve));
+            } else if let Some(mode) = key.strip_prefix("scoring.") {
+                let mode = Mode::from_name(mode)
+                    .ok_or_else(|| error(format!("unknown mode '{}'", mode)))?;
+                let scoring = Scoring::from_name(value)
+                    .ok_or_else(|| error(format!("invalid scoring '{}'", value)))?;
+                config.scoring.retain(|&(other, _)| other != mode);
+                config.scoring.push((mode, scoring));
+            } else if let Some(setting) = Self::parse_setting(&mut config.settings, key, value) {
+                setting.map_err(error)?;
+            } else {
+                return Err(error(format!("unknown key '{}'", key)));
+            }
+        }
+        Ok(config)
+    }
+
+    // Set one of the settings, None if the key is not a setting
+    fn parse_setting(
+        settings: &mut Settings,
+        key: &str,
+        value: &str,
+    ) -> Option<Result<(), String>> {
+        let on_off = |value: &str| match value {
+            "on" => Some(true),
+            "off" => Some(false),
+            _ => None,
+        };
+        let valid = match key {
+            "das" if value == "off" => {
+                settings.das = None;
+                true
+            }
+            "das" => value.parse().map(|das| settings.das = Some(das)).is_ok(),
+            "arr" => value.parse().map(|arr| settings.arr = arr).is_ok(),
+            "are" if value == "gravity" => {
+                settings.spawn_delay = None;
+                true
+            }
+            "are" => value
+                .parse()
+                .map(|delay| settings.spawn_delay = Some(delay))
+                .is_ok(),
+            "mouse" => on_off(value).map(|on| settings.mouse = on).is_some(),
+            "ghost" => on_off(value).map(|on| settings.ghost = on).is_some(),
+            "sound" => on_off(value).map(|on| settings.sound = on).is_some(),
+            "theme" if value == "auto" => {
+                settings.palette = None;
+                true
+            }
+            "theme" => Palette::from_name(value)
+                .map(|palette| settings.palette = Some(palette))
+                .is_some(),
+            "style" => RenderStyle::from_name(value)
+                .map(|style| settings.style = style)
+                .is_some(),
+            "preview" => match value.parse() {
+                Ok(count) if count <= TetrominoSequence::MAX_PREVIEW => {
+                    settings.preview_count = count;
+                    true
+                }
+                _ => false,
+            },
+            "danger" => match value.parse() {
+                Ok(margin) if margin <= Settings::MAX_DANGER_MARGIN => {
+                    settings.danger_margin = margin;
+                    true
+                }
+                _ => false,
+            },
+            _ => return None,
+        };
+        Some(if valid {
+            Ok(())
+        } else {
+            Err(format!("invalid value '{}' for '{}'", value, key))
+        })
+    }
+
+    // Write the settings changed from `before` to `after` to the configuration file. Their lines
+    // are replaced, and the missing ones added at the end; the other lines are left as they are,
+    // so that the overrides of the command line don't end up in the file.
+    pub fn save_settings(before: &Settings, after: &Settings) -> Result<(), String> {
+        let path =
+            Self::path().ok_or("cannot locate the configuration directory (HOME is not set)")?;
+        let error = |err: std::io::Error| format!("{}: {}", path.display(), err);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(error(err)),
+        };
+
+        let mut entries: Vec<Option<(&str, String)>> = after
+            .entries()
+            .into_iter()
+            .zip(before.entries())
+            .filter(|(entry, old)| entry != old)
+            .map(|(entry, _)| Some(entry))
+            .collect();
+        let mut lines: Vec<String> = text
+            .lines()
+            .map(|line| {
+                let key = line.split_once('=').map(|(key, _)| key.trim());
+                let entry = entries
+                    .iter_mut()
+                    .find(|entry| entry.as_ref().map(|(name, _)| Some(*name)) == Some(key));
+                match entry.and_then(Option::take) {
+                    Some((key, value)) => format!("{} = {}", key, value),
+                    None => line.to_string(),
+                }
+            })
+            .collect();
+        lines.extend(
+            entries
+                .into_iter()
+                .flatten()
+                .map(|(key, value)| format!("{} = {}", key, value)),
+        );
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(error)?;
+        }
+        std::fs::write(&path, lines.join("\n") + "\n").map_err(error)
+    }
+}
+
+// A curve name, or a comma separated list of milliseconds per row
+fn parse_gravity(value: &str) -> Option<GravityCurve> {
+    GravityCurve::from_name(value).or_else(|| {
+        let millis = value
+            .split(',')
+            .map(|millis| millis.trim().parse().ok().filter(|&millis| millis > 0))
+            .collect::<Option<Vec<u32>>>()?;
+        Some(GravityCurve::Table(millis))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_lines_are_read() {
+        let config = Config::parse(
+            "# make S and Z easier to tell apart
+            color.S = 34
+            color.Z = #ff5f87
+
+            color.T=dark_magenta
+            gravity.marathon = guideline
+            gravity.classic = 800, 700 ,600
+            gravity.marathon = nes
+            scoring.sprint = guideline
+            das = 170
+            arr = 0
+            are = 200
+            mouse = on
+            ghost = off
+            theme = pastel
+            preview = 6
+            danger = 0
+            sound = off
+            style = half-block",
+        )
+        .unwrap();
+
+        let mut colors = [None; 7];
+        colors[Tetromino::S as usize] = Some(Color::AnsiValue(34));
+        colors[Tetromino::Z as usize] = Some(Color::Rgb {
+            r: 0xff,
+            g: 0x5f,
+            b: 0x87,
+        });
+        colors[Tetromino::T as usize] = Some(Color::DarkMagenta);
+        assert_eq!(config.piece_colors, colors);
+        // the last line of a mode wins
+        assert_eq!(
+            config.gravity,
+            [
+                (Mode::Classic, GravityCurve::Table(vec![800, 700, 600])),
+                (Mode::Marathon, GravityCurve::Nes),
+            ]
+        );
+        assert_eq!(config.scoring, [(Mode::Sprint, Scoring::Guideline)]);
+        assert_eq!(
+            config.settings,
+            Settings {
+                das: Some(170),
+                arr: 0,
+                spawn_delay: Some(200),
+                mouse: true,
+                ghost: false,
+                palette: Some(Palette::Pastel),
+                preview_count: 6,
+                danger_margin: 0,
+                sound: false,
+                style: RenderStyle::HalfBlock,
+            }
+        );
+
+        let config = Config::parse("das = off\nare = gravity\ntheme = auto").unwrap();
+        assert_eq!(config.settings, Settings::default());
+    }
+
+    #[test]
+    fn invalid_lines_are_reported_with_their_number() {
+        for (text, error) in [
+            ("ghost on", "1: expected 'key = value', got 'ghost on'"),
+            ("\n# colors\ncolor.X = red", "3: unknown tetromino 'X'"),
+            ("color.SZ = red", "1: unknown tetromino 'SZ'"),
+            ("color.S = #ff5f8", "1: invalid color '#ff5f8'"),
+            ("color.S = 256", "1: invalid color '256'"),
+            ("gravity.zen = nes", "1: unknown mode 'zen'"),
+            (
+                "gravity.classic = 800, 0",
+                "1: invalid gravity curve '800, 0'",
+            ),
+            ("gravity.classic = fast", "1: invalid gravity curve 'fast'"),
+            ("scoring.classic = tetris", "1: invalid scoring 'tetris'"),
+            ("arr = 30\nfps = 60", "2: unknown key 'fps'"),
+            ("das = -1", "1: invalid value '-1' for 'das'"),
+            ("arr = 70000", "1: invalid value '70000' for 'arr'"),
+            ("are = soon", "1: invalid value 'soon' for 'are'"),
+            ("mouse = yes", "1: invalid value 'yes' for 'mouse'"),
+            ("theme = neon", "1: invalid value 'neon' for 'theme'"),
+            ("preview = 7", "1: invalid value '7' for 'preview'"),
+            ("danger = 11", "1: invalid value '11' for 'danger'"),
+            ("style = round", "1: invalid value 'round' for 'style'"),
+        ] {
+            assert_eq!(
+                Config::parse(text).err().as_deref(),
+                Some(error),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn colors_are_read_by_name_index_or_value() {
+        assert_eq!(parse_color("red"), Some(Color::Red));
+        assert_eq!(parse_color("grey"), Some(Color::Grey));
+        assert_eq!(parse_color("0"), Some(Color::AnsiValue(0)));
+        assert_eq!(parse_color("255"), Some(Color::AnsiValue(255)));
+        assert_eq!(
+            parse_color("#00A0ff"),
+            Some(Color::Rgb {
+                r: 0,
+                g: 0xa0,
+                b: 0xff
+            })
+        );
+        for value in [
+            "", "#", "#12345", "#1234567", "#12345g", "256", "-1", "reddish",
+        ] {
+            assert_eq!(parse_color(value), None, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+// Input latency diagnostics: the time from receiving a key to flushing the frame showing its
+// effect, gathered over the session in a histogram with power of two buckets (in milliseconds).
+
+use std::time::{Duration, Instant};
+
+pub struct LatencyMeter {
+    // reception of the first key not shown yet
+    pending: Option<Instant>,
+    // counts of latencies under 1ms, 2ms, 4ms... the last bucket taking the rest
+    buckets: [u32; Self::BUCKETS],
+    last: Option<Duration>,
+    total: Duration,
+    count: u32,
+}
+
+impl LatencyMeter {
+    const BUCKETS: usize = 8;
+
+    pub fn new() -> Self {
+        LatencyMeter {
+            pending: None,
+            buckets: [0; Self::BUCKETS],
+            last: None,
+            total: Duration::ZERO,
+            count: 0,
+        }
+    }
+
+    // Keys received before the next frame are measured from the first one
+    pub fn key_received(&mut self, at: Instant) {
+        self.pending.get_or_insert(at);
+    }
+
+    pub fn frame_flushed(&mut self) {
+        let Some(received) = self.pending.take() else {
+            return;
+        };
+        let latency = received.elapsed();
+        let millis = latency.as_millis() as u32;
+        let bucket = (u32::BITS - millis.leading_zeros()) as usize;
+        self.buckets[bucket.min(Self::BUCKETS - 1)] += 1;
+        self.last = Some(latency);
+        self.total += latency;
+        self.count += 1;
+    }
+
+    // One line summary for the game screen
+    pub fn overlay(&self) -> String {
+        match self.last {
+            Some(last) => format!(
+                "Latency {} {:.1}ms",
+                self.histogram(),
+                1000.0 * last.as_secs_f32()
+            ),
+            None => String::from("Latency: press a key"),
+        }
+    }
+
+    pub fn print(&self) {
+        if self.count == 0 {
+            return;
+        }
+        println!(
+            "Key to frame latency ({} keys, average {:.1}ms):\x1b[0K",
+            self.count,
+            1000.0 * self.total.as_secs_f32() / self.count as f32
+        );
+        let max = self.buckets.iter().copied().max().unwrap_or(0).max(1);
+        for (index, &count) in self.buckets.iter().enumerate() {
+            let label = if index + 1 < Self::BUCKETS {
+                format!("< {}ms", 1 << index)
+            } else {
+                format!(">= {}ms", 1 << (index - 1))
+            };
+            println!(
+                "  {:>7}  {:<40} {}\x1b[0K",
+                label,
+                "█".repeat((40 * count / max) as usize),
+                count
+            );
+        }
+    }
+
+    // the buckets as a line of block characters
+    fn histogram(&self) -> String {
+        const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+        let max = self.buckets.iter().copied().max().unwrap_or(0).max(1);
+        self.buckets
+            .iter()
+            .map(|&count| match count {
+                0 => ' ',
+                count => BARS[((count * (BARS.len() as u32 - 1)) / max) as usize],
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,2508 @@
+//! Game engine of ttrys, free of any terminal dependency.
+//!
+//! [`Ttrys`] holds the state of a game. The front-end advances it with [`Ttrys::step`] each time
+//! [`Ttrys::step_duration`] elapses, forwards the player input with [`Ttrys::update`], and reads
+//! back the stack, the current piece and the counters to render them.
+
+pub mod agent;
+pub mod bot;
+pub mod rotation;
+
+use core::fmt;
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+
+use rotation::RotationSystem;
+
+use rand::{
+    distributions::{Distribution, Standard},
+    Rng, SeedableRng,
+};
+// the algorithm of rand's StdRng, whose state can be saved
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+/// Default width of the stack
+pub const STACK_NUM_COLS: usize = 10;
+/// Default height of the stack
+pub const STACK_NUM_ROWS: usize = 20;
+
+/// Content of a cell of the stack
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Mino {
+    Free,
+    Occupied(Tetromino),
+    /// part of a garbage row, see [`Ttrys::add_garbage`]
+    Garbage,
+    /// part of a full row, about to be cleared
+    PendingClear,
+}
+
+/// The seven pieces
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Tetromino {
+    I,
+    J,
+    L,
+    O,
+    S,
+    T,
+    Z,
+}
+
+/// Orientation of a piece: the spawn state, then the states reached by rotating clockwise
+#[derive(Copy, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct RotationState(u8);
+
+impl RotationState {
+    fn cw(self) -> Self {
+        RotationState((self.0 + 1) % 4)
+    }
+
+    fn ccw(self) -> Self {
+        RotationState((self.0 + 3) % 4)
+    }
+
+    fn half(self) -> Self {
+        RotationState((self.0 + 2) % 4)
+    }
+
+    fn turn(self, turn: Turn) -> Self {
+        match turn {
+            Turn::Cw => self.cw(),
+            Turn::Ccw => self.ccw(),
+            Turn::Half => self.half(),
+        }
+    }
+}
+
+/// Direction of a rotation
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Turn {
+    Cw,
+    Ccw,
+    Half,
+}
+
+/// Rotation notation: 0 for the spawn state, R and L after a clockwise or counterclockwise turn,
+/// 2 after a half turn
+impl fmt::Display for RotationState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = "0R2L";
+        write!(f, "{}", names.chars().nth(self.0 as usize).unwrap())
+    }
+}
+
+impl From<RotationState> for usize {
+    fn from(value: RotationState) -> Self {
+        value.0 as usize
+    }
+}
+
+impl<T> From<T> for Tetromino
+where
+    T: std::borrow::Borrow<usize>,
+{
+    fn from(value: T) -> Self {
+        match *value.borrow() % 7 {
+            0 => Tetromino::I,
+            1 => Tetromino::J,
+            2 => Tetromino::L,
+            3 => Tetromino::O,
+            4 => Tetromino::S,
+            5 => Tetromino::T,
+            6 => Tetromino::Z,
+            _ => panic!("bad value"),
+        }
+    }
+}
+
+impl fmt::Display for Tetromino {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letters = "IJLOSTZ";
+        write!(f, "{}", letters.chars().nth(*self as usize).unwrap())
+    }
+}
+
+impl Distribution<Tetromino> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Tetromino {
+        rng.gen::<usize>().into()
+    }
+}
+
+/// Phases of the game
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum State {
+    Spawn,
+    Fall,
+    HardDrop,
+    Lock,
+    ClearRows,
+    Paused,
+    /// Counting down to the start of the game or the end of a pause, 0 being "go", see
+    /// [`Ttrys::set_countdown`]
+    Countdown(u8),
+    End,
+}
+
+/// Goal of a game
+#[derive(Copy, Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Mode {
+    /// Play until topping out, the level follows the score
+    #[default]
+    Classic,
+    /// Go up a level every [`Mode::LINES_PER_LEVEL`] lines, with the guideline gravity, until
+    /// level [`Mode::MARATHON_LEVELS`] is cleared (unless [`Options::endless`])
+    Marathon,
+    /// Clear [`Mode::SPRINT_LINES`] lines as fast as possible
+    Sprint,
+    /// Classic play, but the locked minos fade out after a moment (it is up to the front-end to
+    /// hide them)
+    Invisible,
+    /// Dig through [`Mode::CHEESE_ROWS`] garbage rows, each with a hole, in as few pieces as
+    /// possible
+    Cheese,
+}
+
+impl Mode {
+    /// Lines to clear to finish a sprint
+    pub const SPRINT_LINES: u32 = 40;
+    /// Lines to clear to go up a level in a marathon
+    pub const LINES_PER_LEVEL: u32 = 10;
+    /// Last level of a marathon
+    pub const MARATHON_LEVELS: u32 = 15;
+    /// Garbage rows a cheese game starts with, at most half of the stack
+    pub const CHEESE_ROWS: usize = 10;
+
+    /// Parse "classic", "marathon", "sprint", "invisible" or "cheese"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(Mode::Classic),
+            "marathon" => Some(Mode::Marathon),
+            "sprint" => Some(Mode::Sprint),
+            "invisible" => Some(Mode::Invisible),
+            "cheese" => Some(Mode::Cheese),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mode::Classic => write!(f, "classic"),
+            Mode::Marathon => write!(f, "marathon"),
+            Mode::Sprint => write!(f, "sprint"),
+            Mode::Invisible => write!(f, "invisible"),
+            Mode::Cheese => write!(f, "cheese"),
+        }
+    }
+}
+
+/// Settings of a game, fixed when it starts
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Options {
+    /// Level the game starts at, it stays there until the score (the lines in a marathon) catches
+    /// up. Marathons start at level 1 at least.
+    pub level: u32,
+    /// How the pieces are drawn
+    pub randomizer: RandomizerKind,
+    /// Seed of the random number generator, games with the same seed and options deal the same
+    /// pieces (with a given version of the rand crate). A random one is picked if not given.
+    pub seed: Option<u64>,
+    /// Number of columns of the stack, between [`Options::MIN_SIZE`] and [`Options::MAX_SIZE`]
+    pub width: usize,
+    /// Number of rows of the stack, between [`Options::MIN_SIZE`] and [`Options::MAX_SIZE`]
+    pub height: usize,
+    /// Goal of the game
+    pub mode: Mode,
+    /// Keep a marathon going past its last level
+    pub endless: bool,
+    /// Speed of the pieces along the levels, the default one of the mode if not given, see
+    /// [`GravityCurve::for_mode`]
+    pub gravity: Option<GravityCurve>,
+    /// How clears and drops are rewarded
+    pub scoring: Scoring,
+}
+
+impl Options {
+    /// Smallest stack dimension, pieces must fit in the stack in any orientation
+    pub const MIN_SIZE: usize = 4;
+    /// Largest stack dimension, positions in the stack are stored on 8 bits
+    pub const MAX_SIZE: usize = 100;
+
+    fn first_level(&self) -> u32 {
+        match self.mode {
+            Mode::Marathon => self.level.max(1),
+            _ => self.level,
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            level: 0,
+            randomizer: RandomizerKind::SevenBag,
+            seed: None,
+            width: STACK_NUM_COLS,
+            height: STACK_NUM_ROWS,
+            mode: Mode::Classic,
+            endless: false,
+            gravity: None,
+            scoring: Scoring::Ttrys,
+        }
+    }
+}
+
+/// A game. It can be saved and restored with serde, but for its rotation system, which is back to
+/// SRS once restored.
+#[derive(Serialize, Deserialize)]
+pub struct Ttrys {
+    cur_tetro: Option<Tetromino>,
+    cur_position: (i8, i8),
+    cur_state: RotationState,
+    clear_rows: Vec<i8>,
+    score: (u32, u32), // line cleared / points
+    // consecutive placements clearing lines
+    combo: u32,
+    // tetrises and T-spin clears following the last other clear, if any, minus one
+    back_to_back: Option<u32>,
+    // whether the last thing the current piece did was a rotation, a T-spin takes one
+    rotated: bool,
+    // T-spin made by the last locked piece, scored once its rows are cleared
+    spin: Option<Spin>,
+    level: u32,
+    options: Options,
+    state: State,
+    saved_state: State,
+    // whether the game ended on reaching the goal of its mode
+    completed: bool,
+    // how the game was lost, if it was
+    top_out: Option<TopOut>,
+    // rows of `width` cells, from the bottom up, the vanish zone included
+    stack: Vec<Mino>,
+    width: usize,
+    // visible rows, the vanish zone excepted
+    height: usize,
+    // rows up to the highest occupied cell of each column, kept up to date with the stack
+    column_heights: Vec<u8>,
+    sequence: TetrominoSequence,
+    seed: u64,
+    rng: ChaCha12Rng,
+    // kept apart from the one dealing pieces, so the pieces don't depend on the garbage received
+    garbage_rng: ChaCha12Rng,
+    // batches of garbage rows waiting for the next spawn
+    pending_garbage: Vec<usize>,
+    droughts: DroughtTracker,
+    gravity: GravityPolicy,
+    floor_slide: bool,
+    // count down before the first spawn and after each pause
+    countdown: bool,
+    // wait between a piece locking and the next one spawning, one step of the gravity if None
+    spawn_delay: Option<Duration>,
+    slide_budget: u8,
+    sliding: bool,
+    hold: Option<Tetromino>,
+    hold_used: bool,
+    // rotation and hold asked for before the piece spawns, applied as it does (IRS and IHS)
+    charged_turn: Option<Turn>,
+    charged_hold: bool,
+    stats: Statistics,
+    placements: Vec<Placement>,
+    #[serde(skip)]
+    events: Vec<GameEvent>,
+    #[serde(skip, default = "default_rotation_system")]
+    rotation_system: Box<dyn RotationSystem>,
+    // the playing time follows the clock of Ttrys::simulate rather than the wall clock
+    #[serde(skip)]
+    simulated: bool,
+}
+
+impl Default for Ttrys {
+    fn default() -> Self {
+        Self::new(Options::default())
+    }
+}
+
+impl Ttrys {
+    /// A new game, with level based gravity and the SRS rotation system
+    pub fn new(options: Options) -> Self {
+        let width = options.width.clamp(Options::MIN_SIZE, Options::MAX_SIZE);
+        let height = options.height.clamp(Options::MIN_SIZE, Options::MAX_SIZE);
+        let seed = options.seed.unwrap_or_else(|| rand::rngs::OsRng.gen());
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        let mut ttrys = Ttrys {
+            cur_tetro: None,
+            cur_position: (0, 0),
+            cur_state: RotationState::default(),
+            clear_rows: Vec::new(),
+            score: (0, 0),
+            combo: 0,
+            back_to_back: None,
+            rotated: false,
+            spin: None,
+            level: options.first_level(),
+            state: State::Spawn,
+            saved_state: State::End,
+            completed: false,
+            top_out: None,
+            stack: vec![Mino::Free; width * (height + Self::VANISH_ROWS)],
+            width,
+            height,
+            column_heights: vec![0; width],
+            sequence: TetrominoSequence::new(options.randomizer, &mut rng),
+            seed,
+            rng,
+            garbage_rng: ChaCha12Rng::seed_from_u64(seed.wrapping_add(1)),
+            pending_garbage: Vec::new(),
+            droughts: DroughtTracker::default(),
+            gravity: GravityPolicy::Level,
+            floor_slide: false,
+            countdown: false,
+            spawn_delay: None,
+            slide_budget: 0,
+            sliding: false,
+            hold: None,
+            hold_used: false,
+            charged_turn: None,
+            charged_hold: false,
+            stats: Statistics::new(width),
+            placements: Vec::new(),
+            events: Vec::new(),
+            rotation_system: Box::new(rotation::Srs),
+            simulated: false,
+            options,
+        };
+        // each cheese row gets a hole of its own
+        if ttrys.options.mode == Mode::Cheese {
+            for _ in 0..Mode::CHEESE_ROWS.min(height / 2) {
+                ttrys.insert_garbage(1);
+            }
+        }
+        ttrys
+    }
+
+    /// Start over with the same options, gravity policy, assists, spawn delay and rotation system.
+    /// Unless the options fix the seed, the new game gets a new one.
+    pub fn reset(&mut self) {
+        let gravity = match self.gravity {
+            GravityPolicy::Level => GravityPolicy::Level,
+            GravityPolicy::Adaptive(_) => GravityPolicy::Adaptive(AdaptiveGravity::new()),
+        };
+        let rotation_system = std::mem::replace(&mut self.rotation_system, Box::new(rotation::Srs));
+        let floor_slide = self.floor_slide;
+        let countdown = self.countdown;
+        let spawn_delay = self.spawn_delay;
+        *self = Ttrys::new(self.options.clone());
+        self.gravity = gravity;
+        self.rotation_system = rotation_system;
+        self.floor_slide = floor_slide;
+        self.spawn_delay = spawn_delay;
+        self.set_countdown(countdown);
+    }
+
+    /// How long the full rows stay in the stack before being cleared, whatever the speed, to
+    /// leave the front-end time to animate them
+    pub const CLEAR_DURATION: Duration = Duration::from_millis(300);
+
+    /// Points earned per placement in a combo, see [`Ttrys::combo`]
+    pub const COMBO_BONUS: u32 = 50;
+
+    /// Hidden rows above the visible ones, where the pieces spawn. A piece locking entirely in
+    /// there ends the game.
+    pub const VANISH_ROWS: usize = 4;
+
+    /// First count of the countdown, see [`Ttrys::set_countdown`]
+    pub const COUNTDOWN: u8 = 3;
+
+    /// Time each count of the countdown stays, "go" included
+    pub const COUNTDOWN_DURATION: Duration = Duration::from_millis(500);
+
+    // How much the floor slide assist stretches the step following a touchdown
+    const FLOOR_SLIDE_FACTOR: u32 = 3;
+    // Number of floor slides granted to each piece
+    const FLOOR_SLIDES_PER_PIECE: u8 = 1;
+
+    pub fn set_gravity(&mut self, gravity: GravityPolicy) {
+        self.gravity = gravity;
+    }
+
+    /// Beginner assist: the first time a piece touches down, the wait before it locks is
+    /// stretched and the piece can still be moved, possibly sliding off the ledge.
+    pub fn set_floor_slide(&mut self, enabled: bool) {
+        self.floor_slide = enabled;
+    }
+
+    /// Count down from [`Ttrys::COUNTDOWN`] to "go", one count per step, before the first spawn
+    /// and when a pause ends, so that the pieces don't fall by surprise. Nothing moves meanwhile.
+    pub fn set_countdown(&mut self, enabled: bool) {
+        self.countdown = enabled;
+        // a game yet to start waits for the countdown
+        let fresh = self.cur_tetro.is_none() && self.placements.is_empty();
+        if enabled && self.state == State::Spawn && fresh {
+            self.saved_state = State::Spawn;
+            self.state = State::Countdown(Self::COUNTDOWN);
+        }
+    }
+
+    /// Entry delay (ARE): how long the next piece waits to spawn once one locked (or once its
+    /// rows are cleared), the rotations and hold pressed meanwhile being kept for it. None waits
+    /// for one step of the gravity.
+    pub fn set_spawn_delay(&mut self, delay: Option<Duration>) {
+        self.spawn_delay = delay;
+    }
+
+    pub fn set_rotation_system(&mut self, rotation_system: Box<dyn RotationSystem>) {
+        self.rotation_system = rotation_system;
+    }
+
+    // rows of the stack, the vanish zone included
+    fn rows(&self) -> usize {
+        self.height + Self::VANISH_ROWS
+    }
+
+    // relative positions of the minos of a tetromino, in the current rotation system
+    fn minos(&self, tetro: Tetromino, rotation: RotationState) -> [(i8, i8); 4] {
+        self.rotation_system.minos(tetro, rotation)
+    }
+
+    #[allow(dead_code)]
+    fn random_fill(&mut self) {
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let brick = if self.rng.gen_bool(0.3) {
+                    Mino::Occupied(self.rng.gen())
+                } else {
+                    Mino::Free
+                };
+                self.stack[row * self.width + col] = brick;
+            }
+        }
+        self.update_heights();
+    }
+
+    /// Most garbage rows sent by a single clear, see [`Ttrys::garbage_for`]
+    pub const MAX_GARBAGE: usize = 4;
+
+    /// Garbage rows to send to the opponent for clearing `rows` lines at once: none for a single,
+    /// 1 for a double, 2 for a triple and [`Ttrys::MAX_GARBAGE`] for a tetris
+    pub fn garbage_for(rows: usize) -> usize {
+        [0, 0, 1, 2, Self::MAX_GARBAGE][rows.min(4)]
+    }
+
+    /// Send `rows` rows of garbage, each full but for one hole, to be pushed under the stack when
+    /// the next piece spawns. The holes of a batch are all in the same random column. The game
+    /// ends if blocks get pushed out of the top of the stack.
+    pub fn add_garbage(&mut self, rows: usize) {
+        if rows > 0 && self.running() {
+            self.pending_garbage.push(rows);
+        }
+    }
+
+    /// Number of garbage rows waiting for the next spawn
+    pub fn pending_garbage(&self) -> usize {
+        self.pending_garbage.iter().sum()
+    }
+
+    // Push garbage rows under the stack, return false if blocks went out of the top
+    fn insert_garbage(&mut self, rows: usize) -> bool {
+        let rows = rows.min(self.rows());
+        let kept = (self.rows() - rows) * self.width;
+        let overflow = self.stack[kept..].iter().any(|&block| block != Mino::Free);
+        self.stack.copy_within(0..kept, rows * self.width);
+        let hole = self.garbage_rng.gen_range(0..self.width);
+        for (idx, block) in self.stack[..rows * self.width].iter_mut().enumerate() {
+            *block = if idx % self.width == hole {
+                Mino::Free
+            } else {
+                Mino::Garbage
+            };
+        }
+        self.update_heights();
+        !overflow
+    }
+
+    // Measure the columns again, after the stack changed
+    fn update_heights(&mut self) {
+        let rows = self.rows();
+        for (col, height) in self.column_heights.iter_mut().enumerate() {
+            *height = (0..rows)
+                .rposition(|row| self.stack[row * self.width + col] != Mino::Free)
+                .map_or(0, |row| row as u8 + 1);
+        }
+    }
+
+    /// Advance the game by one step, to be called when [`Ttrys::step_duration`] has elapsed
+    pub fn step(&mut self) {
+        self.tick();
+        // a floor slide only lasts until the next step
+        self.sliding = false;
+        match self.state {
+            State::Spawn => {
+                for rows in std::mem::take(&mut self.pending_garbage) {
+                    self.events.push(GameEvent::GarbageReceived { rows });
+                    if !self.insert_garbage(rows) {
+                        self.top_out = Some(TopOut::GarbageOut);
+                        self.state = State::End;
+                        self.events.push(GameEvent::GameOver { completed: false });
+                        return;
+                    }
+                }
+                let mut tetro = self.deal();
+                self.hold_used = false;
+                // initial hold: the piece dealt goes to the hold right away
+                if std::mem::take(&mut self.charged_hold) {
+                    tetro = match self.hold.replace(tetro) {
+                        Some(held) => held,
+                        None => self.deal(),
+                    };
+                    self.hold_used = true;
+                }
+                self.spawn(tetro);
+                // initial rotation: the piece spawns rotated when there is room for it, without
+                // kicks
+                if let Some(turn) = self.charged_turn.take() {
+                    let state = self.cur_state.turn(turn);
+                    if self.state == State::Fall && !self.collide(state, (0, 0)) {
+                        self.cur_state = state;
+                    }
+                }
+            }
+            State::HardDrop => {
+                if let Some(landing) = self.landing_position() {
+                    let from = self.piece();
+                    let rows = (self.cur_position.1 - landing.1) as u32;
+                    self.score.1 += self.options.scoring.drop_points(rows);
+                    self.rotated &= rows == 0;
+                    self.cur_position = landing;
+                    if let (Some((tetro, from)), Some((_, to))) = (from, self.piece()) {
+                        self.events.push(GameEvent::HardDrop { tetro, from, to });
+                    }
+                }
+                self.state = State::Lock;
+            }
+            State::Fall => {
+                if self.collide(self.cur_state, (0, -1)) {
+                    self.state = State::Lock;
+                    if self.floor_slide && self.slide_budget > 0 {
+                        self.slide_budget -= 1;
+                        self.sliding = true;
+                    }
+                } else {
+                    self.cur_position.1 -= 1;
+                    self.rotated = false;
+                }
+            }
+            State::Lock => {
+                if let Some((tetro, minos)) = self.piece() {
+                    self.events.push(GameEvent::PieceLocked { tetro, minos });
+                }
+                self.spin = self.t_spin();
+                let minos = self.minos(self.cur_tetro.unwrap(), self.cur_state);
+                let holes_before = self.count_holes();
+
+                // finalize the locked piece into the stack
+                minos
+                    .iter()
+                    .map(|(x, y)| {
+                        let h = self.cur_position.1 + y;
+                        h as usize * self.width + (self.cur_position.0 + x) as usize
+                    })
+                    .for_each(|idx| {
+                        let block = &mut self.stack[idx];
+                        *block = Mino::Occupied(self.cur_tetro.unwrap());
+                    });
+                self.update_heights();
+
+                // lock out: the piece is out of sight
+                if minos
+                    .iter()
+                    .all(|(_, y)| (self.cur_position.1 + y) as usize >= self.height)
+                {
+                    self.top_out = Some(TopOut::LockOut);
+                    self.state = State::End;
+                    self.events.push(GameEvent::GameOver { completed: false });
+                    return;
+                }
+
+                // list the full rows after locking the tetromino into the stack
+                // for later removal
+                let set: std::collections::HashSet<i8> = minos
+                    .iter()
+                    .map(|(_, offset)| self.cur_position.1 + offset)
+                    .collect();
+                self.clear_rows = set
+                    .into_iter()
+                    .filter(|&row| {
+                        let start = (row as usize) * self.width;
+                        let end = start + self.width;
+                        self.stack[start..end]
+                            .iter()
+                            .all(|block| *block != Mino::Free)
+                    })
+                    .collect();
+                self.clear_rows.sort();
+
+                self.stats
+                    .piece_locked(self.cur_tetro.unwrap(), self.clear_rows.len());
+                self.placements.push(Placement {
+                    tetro: self.cur_tetro.unwrap(),
+                    column: minos
+                        .iter()
+                        .map(|(x, _)| (self.cur_position.0 + x) as usize)
+                        .min()
+                        .unwrap(),
+                    rotation: self.cur_state,
+                });
+
+                // a placement clearing nothing ends the combo, a clear other than a tetris or a
+                // T-spin ends the back-to-back chain
+                match self.clear_rows.len() {
+                    0 => self.combo = 0,
+                    lines => {
+                        self.combo += 1;
+                        self.back_to_back = if lines >= 4 || self.spin.is_some() {
+                            Some(self.back_to_back.map_or(0, |chain| chain + 1))
+                        } else {
+                            None
+                        };
+                    }
+                }
+
+                let holes_created = self.count_holes().saturating_sub(holes_before);
+                let height = self.stack_height() - self.clear_rows.len() as i8;
+                self.gravity
+                    .piece_locked(holes_created, height, self.height);
+
+                if let Some(spin) = self.spin {
+                    self.events.push(GameEvent::TSpin {
+                        rows: self.clear_rows.len(),
+                        mini: spin == Spin::Mini,
+                    });
+                }
+                if !self.clear_rows.is_empty() {
+                    let rows = self.clear_rows.len();
+                    self.events.push(GameEvent::LinesCleared {
+                        rows,
+                        kind: ClearKind::from_rows(rows),
+                        back_to_back: self.back_to_back() > 0,
+                    });
+                    // Color full rows in a special way
+                    self.clear_rows.iter().for_each(|&row| {
+                        let start = (row as usize) * self.width;
+                        let end = start + self.width;
+                        self.stack[start..end]
+                            .iter_mut()
+                            .for_each(|block| *block = Mino::PendingClear)
+                    });
+                    self.state = State::ClearRows;
+                } else {
+                    // a T-spin clearing nothing still earns points
+                    self.score.1 += self.clear_points(&[]);
+                    self.spin = None;
+                    self.state = State::Spawn;
+                }
+            }
+            State::Countdown(0) => {
+                self.state = self.saved_state;
+                // the first piece comes as soon as the countdown ends
+                if self.state == State::Spawn {
+                    self.step();
+                }
+            }
+            State::Countdown(count) => self.state = State::Countdown(count - 1),
+            State::ClearRows => {
+                // the rows above the cleared ones come down, the runs of adjacent cleared rows are
+                // scored apart
+                let cleared = clear_full_rows(&mut self.stack, self.width);
+                let mut clear_streaks: Vec<i8> = Vec::new();
+                for (index, &row) in cleared.iter().enumerate() {
+                    match clear_streaks.last_mut() {
+                        Some(streak) if cleared[index - 1] + 1 == row => *streak += 1,
+                        _ => clear_streaks.push(1),
+                    }
+                }
+                self.clear_rows.clear();
+                self.update_heights();
+
+                self.cur_tetro = None;
+
+                self.state = State::Spawn;
+
+                // update score
+                self.score.1 += self.clear_points(&clear_streaks);
+                for streak in clear_streaks {
+                    self.score.0 += streak as u32;
+                }
+                self.spin = None;
+                let level = self.level;
+                self.level = match self.options.mode {
+                    Mode::Marathon => {
+                        self.options.first_level() + self.stats.lines() / Mode::LINES_PER_LEVEL
+                    }
+                    _ => self.options.level.max(self.score.1 / 1000),
+                };
+                if self.level > level {
+                    self.events.push(GameEvent::LevelUp { level: self.level });
+                }
+
+                if self.lines_remaining() == Some(0) {
+                    self.completed = true;
+                    self.state = State::End;
+                    self.events.push(GameEvent::GameOver { completed: true });
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Take the next tetromino out of the sequence
+    fn deal(&mut self) -> Tetromino {
+        let tetro = self.sequence.pop(&mut self.rng);
+        self.droughts.record(tetro);
+        tetro
+    }
+
+    // Put a new piece at the top of the stack, ending the game if there is no room for it
+    fn spawn(&mut self, tetro: Tetromino) {
+        self.cur_tetro = Some(tetro);
+        self.rotated = false;
+        self.slide_budget = Self::FLOOR_SLIDES_PER_PIECE;
+        self.sliding = false;
+        self.cur_state = RotationState::default();
+        // as in the guideline, the piece spawns centered (to the left when it can't be) in the
+        // vanish zone, right above the visible rows, whatever its place in the bounding box.
+        // Nothing in the way, it drops a row at once.
+        let minos = self.minos(tetro, self.cur_state);
+        let left = minos.iter().map(|mino| mino.0).min().unwrap_or(0);
+        let right = minos.iter().map(|mino| mino.0).max().unwrap_or(0);
+        let bottom = minos.iter().map(|mino| mino.1).min().unwrap_or(0);
+        let piece_width = right - left + 1;
+        self.cur_position = (
+            (self.width as i8 - piece_width) / 2 - left,
+            self.height as i8 - bottom,
+        );
+        self.events.push(GameEvent::PieceSpawned { tetro });
+        // block out: the piece has no room to spawn
+        if self.collide(self.cur_state, (0, 0)) {
+            self.top_out = Some(TopOut::BlockOut);
+            self.events.push(GameEvent::GameOver { completed: false });
+            self.state = State::End;
+            return;
+        }
+        if !self.collide(self.cur_state, (0, -1)) {
+            self.cur_position.1 -= 1;
+        }
+        self.state = State::Fall;
+    }
+
+    // Position where the current piece would land if dropped straight down
+    fn landing_position(&self) -> Option<(i8, i8)> {
+        self.cur_tetro?;
+        let mut offset = 0;
+        while !self.collide(self.cur_state, (0, offset - 1)) {
+            offset -= 1;
+        }
+        Some((self.cur_position.0, self.cur_position.1 + offset))
+    }
+
+    // Number of free cells lying below an occupied one in the same column
+    fn count_holes(&self) -> u32 {
+        let mut holes = 0;
+        for col in 0..self.width {
+            let mut covered = false;
+            for row in (0..self.rows()).rev() {
+                match self.stack[row * self.width + col] {
+                    Mino::Occupied(_) | Mino::Garbage => covered = true,
+                    Mino::Free if covered => holes += 1,
+                    _ => (),
+                }
+            }
+        }
+        holes
+    }
+
+    fn collide(&self, rotation: RotationState, offset: (i8, i8)) -> bool {
+        if let Some(tetro) = self.cur_tetro {
+            let minos = self.minos(tetro, rotation);
+            let x0 = self.cur_position.0 + offset.0;
+            let y0 = self.cur_position.1 + offset.1;
+            for mino in minos {
+                let x = x0 + mino.0;
+                let y = y0 + mino.1;
+                if (0..self.width as i8).contains(&x) && (0..self.rows() as i8).contains(&y) {
+                    if self.stack[y as usize * self.width + x as usize] != Mino::Free {
+                        return true;
+                    }
+                } else {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    // Return potential wall kick offset
+    fn test_rotation(&self, turn: Turn) -> Option<(i8, i8)> {
+        let tetro = self.cur_tetro?;
+        let next_state = self.cur_state.turn(turn);
+        self.rotation_system
+            .kicks(tetro, self.cur_state, turn)
+            .iter()
+            .copied()
+            .find(|&offset| !self.collide(next_state, offset))
+    }
+
+    // on every call from the front-end: keep the playing time up to date, and forget the events
+    // of the previous call
+    fn tick(&mut self) {
+        if !self.simulated {
+            let running = !matches!(self.state, State::Paused | State::Countdown(_) | State::End);
+            self.stats.tick(running);
+        }
+        self.events.clear();
+    }
+
+    /// Whether the current piece can still be moved by the player
+    pub fn controllable(&self) -> bool {
+        self.state == State::Fall || (self.state == State::Lock && self.sliding)
+    }
+
+    // Between two pieces, during the line clear and spawn delays, the rotations and the hold are
+    // kept for the next piece
+    fn charging(&self) -> bool {
+        matches!(self.state, State::Spawn | State::ClearRows)
+    }
+
+    // a piece moved off the ledge during a floor slide falls again
+    fn resume_fall(&mut self) {
+        if self.state == State::Lock && !self.collide(self.cur_state, (0, -1)) {
+            self.state = State::Fall;
+        }
+    }
+
+    /// Apply a player action
+    pub fn update(&mut self, action: UserAction) {
+        self.tick();
+        match action {
+            UserAction::MoveLeft => {
+                if !self.controllable() {
+                    return;
+                }
+                if !self.collide(self.cur_state, (-1, 0)) {
+                    self.cur_position.0 = self.cur_position.0.saturating_sub(1);
+                    self.rotated = false;
+                    self.resume_fall();
+                    self.events.push(GameEvent::PieceMoved);
+                }
+            }
+            UserAction::MoveRight => {
+                if !self.controllable() {
+                    return;
+                }
+                if !self.collide(self.cur_state, (1, 0)) {
+                    self.cur_position.0 += 1;
+                    self.rotated = false;
+                    self.resume_fall();
+                    self.events.push(GameEvent::PieceMoved);
+                }
+            }
+            UserAction::RotateCW | UserAction::RotateCCW | UserAction::Rotate180 => {
+                let turn = match action {
+                    UserAction::RotateCW => Turn::Cw,
+                    UserAction::RotateCCW => Turn::Ccw,
+                    _ => Turn::Half,
+                };
+                if self.charging() {
+                    self.charged_turn = Some(turn);
+                }
+                if !self.controllable() {
+                    return;
+                }
+                if let Some(offset) = self.test_rotation(turn) {
+                    self.cur_state = self.cur_state.turn(turn);
+                    self.cur_position.0 += offset.0;
+                    self.cur_position.1 += offset.1;
+                    self.rotated = true;
+                    self.resume_fall();
+                    self.events.push(GameEvent::PieceRotated);
+                }
+            }
+            UserAction::HardDrop => {
+                if self.controllable() {
+                    self.state = State::HardDrop;
+                }
+            }
+            UserAction::SoftDrop => {
+                if !self.controllable() {
+                    return;
+                }
+                if !self.collide(self.cur_state, (0, -1)) {
+                    self.cur_position.1 -= 1;
+                    self.rotated = false;
+                    self.score.1 += self.options.scoring.soft_drop_points(1);
+                    self.events.push(GameEvent::PieceMoved);
+                }
+            }
+            UserAction::Hold => {
+                if self.charging() {
+                    self.charged_hold = true;
+                }
+                // the held piece can be swapped only once per spawn
+                if !self.controllable() || self.hold_used {
+                    return;
+                }
+                let current = self.cur_tetro.unwrap();
+                let next = match self.hold.replace(current) {
+                    Some(held) => held,
+                    None => self.deal(),
+                };
+                self.spawn(next);
+                self.hold_used = true;
+            }
+            UserAction::Quit => {
+                self.state = State::End;
+            }
+            UserAction::TogglePause => {
+                // a pause during a countdown is left for a new one
+                if self.state == State::Paused {
+                    self.state = if self.countdown {
+                        State::Countdown(Self::COUNTDOWN)
+                    } else {
+                        self.saved_state
+                    };
+                } else {
+                    if !matches!(self.state, State::Countdown(_)) {
+                        self.saved_state = self.state;
+                    }
+                    self.state = State::Paused;
+                }
+            }
+            // the front-end saves the game, then quits it
+            UserAction::Save => (),
+            UserAction::ToggleHelp => (),
+            UserAction::ToggleDroughtCounter => (), // display only, handled by the GameScreen
+        }
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Time to wait before the next step
+    pub fn step_duration(&self) -> Duration {
+        match self.state {
+            State::ClearRows => return Self::CLEAR_DURATION,
+            State::Countdown(_) => return Self::COUNTDOWN_DURATION,
+            State::Spawn => {
+                if let Some(delay) = self.spawn_delay {
+                    return delay;
+                }
+            }
+            _ => (),
+        }
+        let duration = match &self.options.gravity {
+            Some(curve) => self.gravity.step_duration(self.level(), curve),
+            None => {
+                let curve = GravityCurve::for_mode(self.options.mode);
+                self.gravity.step_duration(self.level(), &curve)
+            }
+        };
+        if self.sliding {
+            duration * Self::FLOOR_SLIDE_FACTOR
+        } else {
+            duration
+        }
+    }
+
+    /// Lines cleared and points
+    pub fn score(&self) -> (u32, u32) {
+        self.score
+    }
+
+    /// Consecutive placements clearing lines, the last one included. A clear making a combo of n
+    /// earns n - 1 times [`Ttrys::COMBO_BONUS`] on top of its reward, times the level with the
+    /// guideline scoring.
+    pub fn combo(&self) -> u32 {
+        self.combo
+    }
+
+    /// Tetrises and T-spin clears in a row since the last other clear, the first one excepted.
+    /// Each one is worth half a tetris on top of its reward, or half its reward with the guideline
+    /// scoring.
+    pub fn back_to_back(&self) -> u32 {
+        self.back_to_back.unwrap_or(0)
+    }
+
+    // Points earned by the last locked piece, given the streaks of consecutive rows it cleared
+    fn clear_points(&self, streaks: &[i8]) -> u32 {
+        let lines = streaks.iter().map(|&streak| streak as usize).sum::<usize>();
+        let back_to_back = lines > 0 && self.back_to_back() > 0;
+        match self.options.scoring {
+            Scoring::Ttrys => {
+                let mut points = streaks
+                    .iter()
+                    .map(|&streak| self.clear_reward(streak))
+                    .sum::<u32>();
+                points += Self::COMBO_BONUS * self.combo.saturating_sub(1);
+                if back_to_back {
+                    points += self.clear_reward(4) / 2;
+                }
+                points
+            }
+            Scoring::Guideline => {
+                let lines = lines.min(4);
+                let mut points = match self.spin {
+                    None => Scoring::GUIDELINE_CLEARS[lines],
+                    Some(Spin::Mini) => Scoring::GUIDELINE_MINI_T_SPINS[lines],
+                    Some(Spin::Full) => Scoring::GUIDELINE_T_SPINS[lines],
+                };
+                if back_to_back {
+                    points += points / 2;
+                }
+                points += Self::COMBO_BONUS * self.combo.saturating_sub(1);
+                points * self.level.max(1)
+            }
+            Scoring::Nes => Scoring::NES_CLEARS[lines.min(4)] * (self.level + 1),
+        }
+    }
+
+    // T-spin made by the current piece if it locked now: a T rotated into place, with three of the
+    // four cells diagonal to its center taken. It is a mini one unless both cells on the side the
+    // T points to are taken.
+    fn t_spin(&self) -> Option<Spin> {
+        if self.cur_tetro != Some(Tetromino::T) || !self.rotated {
+            return None;
+        }
+        let minos = self
+            .minos(Tetromino::T, self.cur_state)
+            .map(|(x, y)| (self.cur_position.0 + x, self.cur_position.1 + y));
+        let adjacent = |a: (i8, i8), b: (i8, i8)| (a.0 - b.0).abs() + (a.1 - b.1).abs() == 1;
+        // the center touches the three other minos, the T points to the one with none opposite
+        let center = *minos
+            .iter()
+            .find(|&&mino| minos.iter().filter(|&&other| adjacent(mino, other)).count() == 3)?;
+        let (dx, dy) = minos
+            .iter()
+            .map(|&(x, y)| (x - center.0, y - center.1))
+            .find(|&(dx, dy)| {
+                (dx, dy) != (0, 0) && !minos.contains(&(center.0 - dx, center.1 - dy))
+            })?;
+        // the walls and the floor count as taken
+        let taken = |(x, y): (i8, i8)| {
+            x < 0
+                || y < 0
+                || x as usize >= self.width
+                || (y as usize) < self.rows()
+                    && self.stack[y as usize * self.width + x as usize] != Mino::Free
+        };
+        let corner = |forward: i8, side: i8| {
+            taken((
+                center.0 + forward * dx + side * dy,
+                center.1 + forward * dy + side * dx,
+            ))
+        };
+        let front = [corner(1, 1), corner(1, -1)];
+        let back = [corner(-1, 1), corner(-1, -1)];
+        let count = front.iter().chain(&back).filter(|&&taken| taken).count();
+        match count {
+            0..=2 => None,
+            _ if front == [true, true] => Some(Spin::Full),
+            _ => Some(Spin::Mini),
+        }
+    }
+
+    fn clear_reward(&self, combo_size: i8) -> u32 {
+        let rewards = [100, 250, 500, 1000];
+        rewards[(combo_size - 1).clamp(0, 3) as usize]
+    }
+
+    /// Lines left to clear to finish a sprint or a marathon, or garbage rows left in a cheese
+    /// game. Endless marathons, and those started past the last level, have no end.
+    pub fn lines_remaining(&self) -> Option<u32> {
+        let goal = match self.options.mode {
+            Mode::Classic | Mode::Invisible => None,
+            Mode::Marathon if self.options.endless => None,
+            Mode::Marathon => (Mode::MARATHON_LEVELS + 1)
+                .checked_sub(self.options.first_level())
+                .filter(|&levels| levels > 0)
+                .map(|levels| levels * Mode::LINES_PER_LEVEL),
+            Mode::Sprint => Some(Mode::SPRINT_LINES),
+            Mode::Cheese => {
+                let rows = self.stack.chunks(self.width);
+                return Some(rows.filter(|row| row.contains(&Mino::Garbage)).count() as u32);
+            }
+        };
+        goal.map(|goal| goal.saturating_sub(self.stats.lines()))
+    }
+
+    /// Whether the game ended on reaching its goal, rather than by topping out or quitting
+    pub fn completed(&self) -> bool {
+        self.completed
+    }
+
+    /// How the stack topped out, None unless the game was lost that way
+    pub fn top_out(&self) -> Option<TopOut> {
+        self.top_out
+    }
+
+    /// Playing time up to now, pauses excluded. Unlike [`Statistics::played`], it runs between
+    /// steps.
+    pub fn elapsed(&self) -> Duration {
+        let since_tick = match self.state {
+            State::Paused | State::Countdown(_) | State::End => None,
+            _ => self.stats.last_tick.map(|last_tick| last_tick.elapsed()),
+        };
+        self.stats.played + since_tick.unwrap_or_default()
+    }
+
+    pub fn running(&self) -> bool {
+        self.state != State::End
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Content of the stack at the given column and row, row 0 being the bottom one. The rows from
+    /// [`Ttrys::height`] up, [`Ttrys::VANISH_ROWS`] of them, are the hidden vanish zone.
+    pub fn mino(&self, col: usize, row: usize) -> Mino {
+        self.stack[row * self.width + col]
+    }
+
+    /// Number of columns of the stack
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Number of visible rows of the stack
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Row of the highest occupied cell of the stack, -1 when it is empty
+    pub fn stack_height(&self) -> i8 {
+        self.column_heights
+            .iter()
+            .max()
+            .map_or(0, |&height| height as i8)
+            - 1
+    }
+
+    /// Number of rows from the bottom of the stack up to the highest occupied cell of each column,
+    /// holes included
+    pub fn column_heights(&self) -> &[u8] {
+        &self.column_heights
+    }
+
+    /// The current piece, and the (column, row) positions of its minos in the stack. There is none
+    /// while full rows are cleared, the piece being part of the stack by then.
+    pub fn piece(&self) -> Option<(Tetromino, [(i8, i8); 4])> {
+        if self.state == State::ClearRows {
+            return None;
+        }
+        let tetro = self.cur_tetro?;
+        let (x0, y0) = self.cur_position;
+        let minos = self.minos(tetro, self.cur_state);
+        Some((tetro, minos.map(|(x, y)| (x0 + x, y0 + y))))
+    }
+
+    /// Positions of the minos of the current piece if it were dropped straight down, as long as
+    /// it can be moved
+    pub fn ghost(&self) -> Option<[(i8, i8); 4]> {
+        if !self.controllable() {
+            return None;
+        }
+        let (x0, y0) = self.landing_position()?;
+        let minos = self.minos(self.cur_tetro?, self.cur_state);
+        Some(minos.map(|(x, y)| (x0 + x, y0 + y)))
+    }
+
+    /// The piece coming after the current one
+    pub fn next(&self) -> Tetromino {
+        self.sequence.peek()
+    }
+
+    /// Seed of the random number generator, see [`Options::seed`]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The next `n` pieces, see [`TetrominoSequence::preview`]
+    pub fn upcoming(&self, n: usize) -> Vec<Tetromino> {
+        self.sequence.preview(n)
+    }
+
+    pub fn hold(&self) -> Option<Tetromino> {
+        self.hold
+    }
+
+    /// Whether the held piece was already swapped since the last spawn
+    pub fn hold_used(&self) -> bool {
+        self.hold_used
+    }
+
+    /// Spawn state of a tetromino, for the previews, see [`RotationSystem::preview`]
+    pub fn preview(&self, tetro: Tetromino) -> [(i8, i8); 4] {
+        self.rotation_system.preview(tetro)
+    }
+
+    pub fn gravity(&self) -> &GravityPolicy {
+        &self.gravity
+    }
+
+    pub fn droughts(&self) -> &DroughtTracker {
+        &self.droughts
+    }
+
+    pub fn stats(&self) -> &Statistics {
+        &self.stats
+    }
+
+    /// What happened during the last call to [`Ttrys::step`] or [`Ttrys::update`], for the
+    /// front-end to animate, play sounds or tell the other players
+    pub fn events(&self) -> &[GameEvent] {
+        &self.events
+    }
+
+    /// Locked pieces, in order
+    pub fn placements(&self) -> &[Placement] {
+        &self.placements
+    }
+
+    /// Settings the game was created with
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// 64-bit hash of the stack, the current piece (type, position and orientation), the held
+    /// piece and the upcoming ones, the score, level, lines and pending garbage. It only depends on
+    /// that state, and is the same across runs, platforms and versions (FNV-1a), so that games can
+    /// be compared to detect desyncs, check replays or spot positions seen before.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write(&(self.width as u16).to_le_bytes());
+        hash.write(&(self.height as u16).to_le_bytes());
+        for mino in &self.stack {
+            hash.write(&[match mino {
+                Mino::Free => 0,
+                Mino::Occupied(tetro) => 1 + *tetro as u8,
+                Mino::PendingClear => 8,
+                Mino::Garbage => 9,
+            }]);
+        }
+        match self.cur_tetro {
+            Some(tetro) => hash.write(&[
+                1 + tetro as u8,
+                self.cur_position.0 as u8,
+                self.cur_position.1 as u8,
+                self.cur_state.0,
+            ]),
+            None => hash.write(&[0]),
+        }
+        hash.write(&[
+            self.hold.map_or(0, |tetro| 1 + tetro as u8),
+            self.hold_used as u8,
+        ]);
+        for tetro in self.sequence.preview(TetrominoSequence::MAX_PREVIEW) {
+            hash.write(&[tetro as u8]);
+        }
+        for value in [
+            self.score.1,
+            self.level,
+            self.score.0,
+            self.pending_garbage() as u32,
+        ] {
+            hash.write(&value.to_le_bytes());
+        }
+        hash.finish()
+    }
+
+    /// Play the game on a simulated clock, with no waiting: the steps come every
+    /// [`Ttrys::step_duration`] of simulated time, and each action is applied once the clock
+    /// reaches its time, before the step due then. The simulation ends with the game, or when the
+    /// clock reaches `until`, the gravity going on after the last action. Games with a seed in
+    /// their options and the same actions always end up the same.
+    pub fn simulate(&mut self, actions: &[TimedAction], until: Duration) -> GameSummary {
+        self.simulated = true;
+        let mut clock = Duration::ZERO;
+        let mut log = Vec::new();
+        let mut actions = actions
+            .iter()
+            .filter(|action| action.at <= until)
+            .peekable();
+        let mut step_due = self.step_duration();
+        while self.running() {
+            let next = actions.peek().copied().filter(|next| next.at <= step_due);
+            let now = match next {
+                Some(next) => next.at.max(clock),
+                None if step_due > until => break,
+                None => step_due.max(clock),
+            };
+            if !matches!(self.state, State::Paused | State::Countdown(_)) {
+                self.stats.advance(now - clock);
+            }
+            clock = now;
+            if let Some(next) = next {
+                self.update(next.action);
+                actions.next();
+            } else {
+                self.step();
+                step_due = clock + self.step_duration();
+            }
+            log.extend(self.events.iter().map(|&event| (clock, event)));
+        }
+        self.simulated = false;
+        GameSummary {
+            score: self.score.1,
+            lines: self.stats.lines(),
+            state_hash: self.state_hash(),
+            events: log,
+            played: self.stats.played,
+            running: self.running(),
+        }
+    }
+}
+
+/// A player action for [`Ttrys::simulate`]
+#[derive(Copy, Clone, Debug)]
+pub struct TimedAction {
+    /// Time of the action, from the start of the simulation
+    pub at: Duration,
+    pub action: UserAction,
+}
+
+/// Outcome of [`Ttrys::simulate`]
+#[derive(Clone, Debug)]
+pub struct GameSummary {
+    /// Points scored
+    pub score: u32,
+    /// Lines cleared
+    pub lines: u32,
+    /// See [`Ttrys::state_hash`]
+    pub state_hash: u64,
+    /// The events of the game, with their time from the start of the simulation
+    pub events: Vec<(Duration, GameEvent)>,
+    /// Playing time at the end, pauses excluded
+    pub played: Duration,
+    /// Whether the game is still going, the simulation having run out of time
+    pub running: bool,
+}
+
+/// Something that happened in the game, see [`Ttrys::events`]. Positions are (column, row) in the
+/// stack.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameEvent {
+    /// A new piece appeared at the top of the stack, dealt or taken out of the hold
+    PieceSpawned { tetro: Tetromino },
+    /// The piece moved left or right
+    PieceMoved,
+    /// The piece rotated
+    PieceRotated,
+    /// A piece was hard dropped from the `from` position to the `to` position
+    HardDrop {
+        tetro: Tetromino,
+        from: [(i8, i8); 4],
+        to: [(i8, i8); 4],
+    },
+    /// A piece was locked into the stack
+    PieceLocked {
+        tetro: Tetromino,
+        minos: [(i8, i8); 4],
+    },
+    /// The piece just locked completed `rows` rows, cleared at the next step. `back_to_back`
+    /// tells a clear continuing a back-to-back chain, see [`Ttrys::back_to_back`].
+    LinesCleared {
+        rows: usize,
+        kind: ClearKind,
+        back_to_back: bool,
+    },
+    /// The piece just locked is a T-spin, or a mini T-spin, clearing `rows` rows (maybe none).
+    /// It comes before the [`GameEvent::LinesCleared`] event of the clear.
+    TSpin { rows: usize, mini: bool },
+    /// The level went up
+    LevelUp { level: u32 },
+    /// Garbage rows were pushed under the stack, see [`Ttrys::add_garbage`]
+    GarbageReceived { rows: usize },
+    /// The stack topped out, or the goal of the mode was reached
+    GameOver { completed: bool },
+}
+
+/// Name of a line clear, after the number of rows
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ClearKind {
+    Single,
+    Double,
+    Triple,
+    Tetris,
+}
+
+impl ClearKind {
+    fn from_rows(rows: usize) -> Self {
+        match rows {
+            0 | 1 => ClearKind::Single,
+            2 => ClearKind::Double,
+            3 => ClearKind::Triple,
+            _ => ClearKind::Tetris,
+        }
+    }
+}
+
+// 64-bit FNV-1a hash, stable unlike the hashers of the standard library
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A piece locked into the stack
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Placement {
+    pub tetro: Tetromino,
+    /// Leftmost column covered by the piece, from 0
+    pub column: usize,
+    pub rotation: RotationState,
+}
+
+/// Piece and line clear statistics, used to grade how efficiently the player stacks
+#[derive(Serialize, Deserialize)]
+pub struct Statistics {
+    // width of the stack, in minos per line
+    width: usize,
+    pieces: u32,
+    // locked pieces of each type, indexed by tetromino
+    tetrominoes: [u32; 7],
+    // number of singles, doubles, triples and tetrises
+    clears: [u32; 4],
+    // playing time, pauses excluded
+    played: Duration,
+    #[serde(skip)]
+    last_tick: Option<Instant>,
+}
+
+impl Statistics {
+    fn new(width: usize) -> Self {
+        Statistics {
+            width,
+            pieces: 0,
+            tetrominoes: [0; 7],
+            clears: [0; 4],
+            played: Duration::ZERO,
+            last_tick: None,
+        }
+    }
+
+    // Account for the time elapsed since the previous tick, unless the game was not running then
+    fn tick(&mut self, running: bool) {
+        let now = Instant::now();
+        if let Some(last_tick) = self.last_tick.filter(|_| running) {
+            self.played += now - last_tick;
+        }
+        self.last_tick = Some(now);
+    }
+
+    // Account for simulated time, in place of the wall clock
+    fn advance(&mut self, elapsed: Duration) {
+        self.played += elapsed;
+        self.last_tick = None;
+    }
+
+    fn piece_locked(&mut self, tetro: Tetromino, lines: usize) {
+        self.pieces += 1;
+        self.tetrominoes[tetro as usize] += 1;
+        if lines > 0 {
+            self.clears[lines.min(4) - 1] += 1;
+        }
+    }
+
+    /// Number of locked pieces
+    pub fn pieces(&self) -> u32 {
+        self.pieces
+    }
+
+    /// Number of locked pieces of a given type
+    pub fn tetrominoes(&self, tetro: Tetromino) -> u32 {
+        self.tetrominoes[tetro as usize]
+    }
+
+    /// Playing time, pauses excluded
+    pub fn played(&self) -> Duration {
+        self.played
+    }
+
+    /// Average number of pieces locked per second of play
+    pub fn pieces_per_second(&self) -> f32 {
+        match self.played.as_secs_f32() {
+            secs if secs > 0.0 => self.pieces as f32 / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// Number of singles, doubles, triples and tetrises
+    pub fn clears(&self) -> [u32; 4] {
+        self.clears
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.clears
+            .iter()
+            .enumerate()
+            .map(|(idx, count)| (idx as u32 + 1) * count)
+            .sum()
+    }
+
+    /// Lines not cleared by a tetris
+    pub fn burned_lines(&self) -> u32 {
+        self.lines() - 4 * self.clears[3]
+    }
+
+    /// Share of the lines cleared by tetrises
+    pub fn tetris_rate(&self) -> f32 {
+        match self.lines() {
+            0 => 0.0,
+            lines => (4 * self.clears[3]) as f32 / lines as f32,
+        }
+    }
+
+    /// Share of the placed minos that were eventually cleared: each piece brings 4 minos and each
+    /// line takes as many as the stack is wide.
+    pub fn stacking_efficiency(&self) -> f32 {
+        match self.pieces {
+            0 => 0.0,
+            pieces => (self.width as u32 * self.lines()) as f32 / (4 * pieces) as f32,
+        }
+    }
+
+    /// Letter grade, from S to D, mixing stacking efficiency and tetris rate
+    pub fn grade(&self) -> &'static str {
+        if self.lines() == 0 {
+            return "-";
+        }
+        let score = 0.5 * self.stacking_efficiency() + 0.5 * self.tetris_rate();
+        match score {
+            s if s >= 0.85 => "S",
+            s if s >= 0.7 => "A",
+            s if s >= 0.55 => "B",
+            s if s >= 0.4 => "C",
+            _ => "D",
+        }
+    }
+}
+
+/// The ways of losing a game
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum TopOut {
+    /// A piece had no room to spawn
+    BlockOut,
+    /// A piece locked entirely in the vanish zone, see [`Ttrys::VANISH_ROWS`]
+    LockOut,
+    /// Garbage rows pushed blocks out of the top of the vanish zone
+    GarbageOut,
+}
+
+impl fmt::Display for TopOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopOut::BlockOut => write!(f, "block out"),
+            TopOut::LockOut => write!(f, "lock out"),
+            TopOut::GarbageOut => write!(f, "pushed out by garbage"),
+        }
+    }
+}
+
+/// Decides how fast the pieces fall
+#[derive(Serialize, Deserialize)]
+pub enum GravityPolicy {
+    // the speed follows the level, along the gravity curve of the game
+    Level,
+    // the speed follows the player's recent performance
+    Adaptive(AdaptiveGravity),
+}
+
+impl GravityPolicy {
+    fn step_duration(&self, level: u32, curve: &GravityCurve) -> Duration {
+        match self {
+            GravityPolicy::Level => curve.duration(level as f32),
+            GravityPolicy::Adaptive(adaptive) => curve.duration(adaptive.speed),
+        }
+    }
+
+    fn piece_locked(&mut self, holes_created: u32, stack_height: i8, num_rows: usize) {
+        if let GravityPolicy::Adaptive(adaptive) = self {
+            adaptive.piece_locked(holes_created, stack_height, num_rows);
+        }
+    }
+}
+
+/// Nudges the speed (expressed as a fractional level) after each locked piece: clean placements
+/// speed things up a little, while misdrops creating holes, a high stack or a quickly rising
+/// stack slow it down, keeping the player challenged without being overwhelmed.
+#[derive(Serialize, Deserialize)]
+pub struct AdaptiveGravity {
+    speed: f32,
+    heights: std::collections::VecDeque<i8>,
+}
+
+impl Default for AdaptiveGravity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AdaptiveGravity {
+    const MIN_SPEED: f32 = 0.0;
+    const MAX_SPEED: f32 = 10.0;
+    // number of pieces over which the height trend is measured
+    const TREND_WINDOW: usize = 10;
+
+    pub fn new() -> Self {
+        AdaptiveGravity {
+            speed: Self::MIN_SPEED,
+            heights: std::collections::VecDeque::with_capacity(Self::TREND_WINDOW),
+        }
+    }
+
+    /// Current speed, as a fractional level
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    fn piece_locked(&mut self, holes_created: u32, stack_height: i8, num_rows: usize) {
+        if self.heights.len() == Self::TREND_WINDOW {
+            self.heights.pop_front();
+        }
+        self.heights.push_back(stack_height);
+        let trend = stack_height - self.heights.front().unwrap();
+
+        let mut delta = if holes_created > 0 {
+            -0.4 * holes_created as f32
+        } else {
+            0.1
+        };
+        if stack_height as usize > num_rows / 2 {
+            delta -= 0.2;
+        }
+        if trend > 4 {
+            delta -= 0.2;
+        }
+        self.speed = (self.speed + delta).clamp(Self::MIN_SPEED, Self::MAX_SPEED);
+    }
+}
+
+/// Time for a piece to fall by one row, along the levels
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GravityCurve {
+    /// NES frame counts, from 800ms at level 0 down to one frame from level 29
+    Nes,
+    /// Guideline curve, from 1s at level 1 down to a few milliseconds at level 20
+    Guideline,
+    /// From 600ms at level 0 down to 150ms at level 10, easing off along the way
+    Ttrys,
+    /// Milliseconds per row at levels 0, 1, 2..., the last one holding for the levels past it
+    Table(Vec<u32>),
+}
+
+impl GravityCurve {
+    // frames per row at 60.0988 frames per second, NTSC version
+    const NES_FRAMES: [u8; 30] = [
+        48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+        2, 1,
+    ];
+    const NES_FRAME_RATE: f32 = 60.0988;
+
+    /// The curve of a mode unless told otherwise: the guideline one in marathons, the ttrys one
+    /// elsewhere
+    pub fn for_mode(mode: Mode) -> Self {
+        match mode {
+            Mode::Marathon => GravityCurve::Guideline,
+            _ => GravityCurve::Ttrys,
+        }
+    }
+
+    /// Parse "nes", "guideline" or "ttrys"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nes" => Some(GravityCurve::Nes),
+            "guideline" => Some(GravityCurve::Guideline),
+            "ttrys" => Some(GravityCurve::Ttrys),
+            _ => None,
+        }
+    }
+
+    /// Time to fall by one row at a level, fractional levels fall between their neighbours
+    pub fn duration(&self, level: f32) -> Duration {
+        match self {
+            GravityCurve::Nes => {
+                let frames = interpolate(Self::NES_FRAMES.len(), level, |level| {
+                    Self::NES_FRAMES[level] as f32
+                });
+                Duration::from_secs_f32(frames / Self::NES_FRAME_RATE)
+            }
+            GravityCurve::Guideline => guideline_duration(level),
+            GravityCurve::Ttrys => duration_from_level(level),
+            GravityCurve::Table(millis) if millis.is_empty() => duration_from_level(level),
+            GravityCurve::Table(millis) => {
+                let millis = interpolate(millis.len(), level, |level| millis[level] as f32);
+                Duration::from_micros((millis * 1000.0) as u64)
+            }
+        }
+    }
+}
+
+/// How clears and drops are rewarded
+#[derive(Copy, Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Scoring {
+    /// 100, 250, 500 and 1000 points for each streak of 1 to 4 adjacent rows cleared, with
+    /// [`Ttrys::COMBO_BONUS`] per combo and half a tetris per back-to-back clear
+    #[default]
+    Ttrys,
+    /// 100, 300, 500 and 800 points for 1 to 4 lines, 400 to 1600 for the T-spins (100 to 400
+    /// for the mini ones), 1.5 times more back-to-back, plus the combos, all times the level.
+    /// Hard drops earn 2 points per row, soft drops 1.
+    Guideline,
+    /// 40, 100, 300 and 1200 points for 1 to 4 lines, times the level plus one
+    Nes,
+}
+
+/// The name parsed by [`Scoring::from_name`]
+impl fmt::Display for Scoring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scoring::Ttrys => write!(f, "ttrys"),
+            Scoring::Guideline => write!(f, "guideline"),
+            Scoring::Nes => write!(f, "nes"),
+        }
+    }
+}
+
+impl Scoring {
+    // points by number of lines cleared
+    const GUIDELINE_CLEARS: [u32; 5] = [0, 100, 300, 500, 800];
+    const GUIDELINE_T_SPINS: [u32; 5] = [400, 800, 1200, 1600, 1600];
+    const GUIDELINE_MINI_T_SPINS: [u32; 5] = [100, 200, 400, 400, 400];
+    const NES_CLEARS: [u32; 5] = [0, 40, 100, 300, 1200];
+
+    /// Parse "ttrys", "guideline" or "nes"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ttrys" => Some(Scoring::Ttrys),
+            "guideline" => Some(Scoring::Guideline),
+            "nes" => Some(Scoring::Nes),
+            _ => None,
+        }
+    }
+
+    fn drop_points(&self, rows: u32) -> u32 {
+        match self {
+            Scoring::Guideline => 2 * rows,
+            Scoring::Ttrys | Scoring::Nes => 0,
+        }
+    }
+
+    fn soft_drop_points(&self, rows: u32) -> u32 {
+        match self {
+            Scoring::Guideline => rows,
+            Scoring::Ttrys | Scoring::Nes => 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+enum Spin {
+    Mini,
+    Full,
+}
+
+/// The ways of drawing pieces
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum RandomizerKind {
+    /// Guideline randomizer: the seven tetrominoes are shuffled and dealt in turn
+    SevenBag,
+    /// Bags of a given number (1 to 7) of distinct tetrominoes, picked at random
+    Bag(usize),
+    /// Every piece is drawn independently (uniform distribution), droughts are unbounded
+    Classic,
+    /// TGM style: a piece among the last 4 dealt is drawn again, up to 6 times
+    TgmHistory,
+}
+
+/// The name parsed by [`RandomizerKind::from_name`]
+impl fmt::Display for RandomizerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RandomizerKind::SevenBag => write!(f, "seven-bag"),
+            RandomizerKind::Bag(size) => write!(f, "bag-{}", size),
+            RandomizerKind::Classic => write!(f, "classic"),
+            RandomizerKind::TgmHistory => write!(f, "tgm"),
+        }
+    }
+}
+
+impl RandomizerKind {
+    /// Parse "seven-bag", "bag-<1..7>", "classic" or "tgm"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "seven-bag" => Some(RandomizerKind::SevenBag),
+            "classic" => Some(RandomizerKind::Classic),
+            "tgm" => Some(RandomizerKind::TgmHistory),
+            _ => name
+                .strip_prefix("bag-")
+                .and_then(|size| size.parse().ok())
+                .filter(|size| (1..=7).contains(size))
+                .map(RandomizerKind::Bag),
+        }
+    }
+}
+
+/// Deals the pieces, following a [`RandomizerKind`]
+#[derive(Serialize, Deserialize)]
+pub struct TetrominoSequence {
+    kind: RandomizerKind,
+    // pieces to be dealt, drawn ahead to keep at least MAX_PREVIEW of them
+    upcoming: std::collections::VecDeque<Tetromino>,
+    // last pieces drawn by the TGM randomizer, the first one drawn is treated apart
+    history: std::collections::VecDeque<Tetromino>,
+    first: bool,
+}
+
+impl TetrominoSequence {
+    /// Number of upcoming pieces that can be previewed
+    pub const MAX_PREVIEW: usize = 6;
+    const TGM_HISTORY: [Tetromino; 4] = [Tetromino::Z, Tetromino::S, Tetromino::S, Tetromino::Z];
+    const TGM_ROLLS: usize = 6;
+
+    pub fn new(kind: RandomizerKind, rng: &mut impl Rng) -> Self {
+        let mut this = TetrominoSequence {
+            kind,
+            upcoming: std::collections::VecDeque::with_capacity(Self::MAX_PREVIEW + 7),
+            history: std::collections::VecDeque::from(Self::TGM_HISTORY),
+            first: true,
+        };
+        this.fill(rng);
+        this
+    }
+
+    /// The next piece, without dealing it
+    pub fn peek(&self) -> Tetromino {
+        self.upcoming[0]
+    }
+
+    /// The next `n` pieces (at most [`TetrominoSequence::MAX_PREVIEW`]), without dealing them
+    pub fn preview(&self, n: usize) -> Vec<Tetromino> {
+        self.upcoming.iter().take(n).copied().collect()
+    }
+
+    pub fn pop(&mut self, rng: &mut impl Rng) -> Tetromino {
+        let ret = self.upcoming.pop_front().unwrap();
+        self.fill(rng);
+        ret
+    }
+
+    fn fill(&mut self, rng: &mut impl Rng) {
+        while self.upcoming.len() < Self::MAX_PREVIEW {
+            match self.kind {
+                RandomizerKind::SevenBag => {
+                    let mut bag: [Tetromino; 7] = core::array::from_fn(Tetromino::from);
+                    bag.shuffle(rng);
+                    self.upcoming.extend(bag);
+                }
+                RandomizerKind::Bag(size) => {
+                    let mut ids: [usize; 7] = core::array::from_fn(|i| i + 1);
+                    ids.partial_shuffle(rng, size.clamp(1, 7))
+                        .0
+                        .iter()
+                        .for_each(|idx| self.upcoming.push_back(idx.into()));
+                }
+                RandomizerKind::Classic => self.upcoming.push_back(rng.gen()),
+                RandomizerKind::TgmHistory => {
+                    let tetro = self.roll_tgm(rng);
+                    self.upcoming.push_back(tetro);
+                }
+            }
+        }
+    }
+
+    fn roll_tgm(&mut self, rng: &mut impl Rng) -> Tetromino {
+        let tetro = if self.first {
+            // never start with a piece creating an overhang
+            self.first = false;
+            *[Tetromino::I, Tetromino::J, Tetromino::L, Tetromino::T]
+                .choose(rng)
+                .unwrap()
+        } else {
+            let mut tetro = rng.gen();
+            for _ in 1..Self::TGM_ROLLS {
+                if !self.history.contains(&tetro) {
+                    break;
+                }
+                tetro = rng.gen();
+            }
+            tetro
+        };
+        self.history.pop_front();
+        self.history.push_back(tetro);
+        tetro
+    }
+}
+
+/// Keeps track of how the randomizer deals pieces: a drought is the number of pieces dealt since a
+/// given tetromino was last seen, a flood the number of times it was dealt within the last 7 pieces.
+#[derive(Default, Serialize, Deserialize)]
+pub struct DroughtTracker {
+    current: [u32; 7],
+    longest: [u32; 7],
+    recent: std::collections::VecDeque<Tetromino>,
+    worst_flood: [u32; 7],
+}
+
+impl DroughtTracker {
+    const FLOOD_WINDOW: usize = 7;
+
+    pub fn record(&mut self, tetro: Tetromino) {
+        for (idx, count) in self.current.iter_mut().enumerate() {
+            if idx == tetro as usize {
+                *count = 0;
+            } else {
+                *count += 1;
+                self.longest[idx] = self.longest[idx].max(*count);
+            }
+        }
+
+        if self.recent.len() == Self::FLOOD_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(tetro);
+        let flood = self.recent.iter().filter(|&&t| t == tetro).count() as u32;
+        let worst = &mut self.worst_flood[tetro as usize];
+        *worst = (*worst).max(flood);
+    }
+
+    pub fn current(&self, tetro: Tetromino) -> u32 {
+        self.current[tetro as usize]
+    }
+
+    pub fn longest(&self, tetro: Tetromino) -> u32 {
+        self.longest[tetro as usize]
+    }
+
+    pub fn worst_flood(&self, tetro: Tetromino) -> u32 {
+        self.worst_flood[tetro as usize]
+    }
+}
+
+/// Player inputs
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum UserAction {
+    MoveLeft,
+    MoveRight,
+    RotateCW,
+    RotateCCW,
+    Rotate180,
+    HardDrop,
+    // down by one row, without locking
+    SoftDrop,
+    Hold,
+    TogglePause,
+    ToggleDroughtCounter,
+    // the key help, display only too
+    ToggleHelp,
+    Quit,
+    // leave the game, to be resumed later
+    Save,
+}
+
+/// Remove the full rows of a stack of rows `width` cells wide, from the bottom up: the rows above
+/// come down, and free rows take their place at the top. Return the indexes the cleared rows had,
+/// from the bottom up.
+pub fn clear_full_rows(stack: &mut [Mino], width: usize) -> Vec<usize> {
+    let mut cleared = Vec::new();
+    let mut kept = 0;
+    for row in 0..stack.len() / width {
+        let cells = row * width..(row + 1) * width;
+        if stack[cells.clone()].iter().all(|&mino| mino != Mino::Free) {
+            cleared.push(row);
+        } else {
+            stack.copy_within(cells, kept * width);
+            kept += 1;
+        }
+    }
+    stack[kept * width..].fill(Mino::Free);
+    cleared
+}
+
+fn default_rotation_system() -> Box<dyn RotationSystem> {
+    Box::new(rotation::Srs)
+}
+
+// Time for a piece to fall by one row in the guideline: (0.8 - (level - 1) * 0.007)^(level - 1)
+// seconds, from 1s at level 1 down to a few milliseconds. Past level 20 the speed stays the same.
+fn guideline_duration(level: f32) -> Duration {
+    let level = level.clamp(1.0, 20.0);
+    Duration::from_secs_f32((0.8 - (level - 1.0) * 0.007).powf(level - 1.0))
+}
+
+// Value of a table of `len` entries at a fractional index, the last entry holding past the end
+fn interpolate(len: usize, level: f32, entry: impl Fn(usize) -> f32) -> f32 {
+    let level = level.max(0.0);
+    let index = (level as usize).min(len - 1);
+    let next = (index + 1).min(len - 1);
+    let fraction = (level - index as f32).min(1.0);
+    entry(index) + (entry(next) - entry(index)) * fraction
+}
+
+fn duration_from_level(level: f32) -> Duration {
+    // the model is:
+    //    * level base_level..=top_level: a power function with fixed power b
+    //    * level < base_level or level > topLevel: constant function
+    const BASE_LEVEL: f32 = 0.0;
+    const TOP_LEVEL: f32 = 10.0;
+    const B: f32 = 0.7;
+    const MIN_DURATION: f32 = 150.0;
+    const MAX_DURATION: f32 = 600.0;
+
+    if level < BASE_LEVEL {
+        Duration::from_millis(MAX_DURATION as _)
+    } else if level > TOP_LEVEL {
+        Duration::from_millis(MIN_DURATION as _)
+    } else {
+        let a = (MAX_DURATION - MIN_DURATION) / (BASE_LEVEL.powf(B) - TOP_LEVEL.powf(B));
+        let c = ((MAX_DURATION * TOP_LEVEL.powf(B)) - (MIN_DURATION * BASE_LEVEL.powf(B)))
+            / (TOP_LEVEL.powf(B) - BASE_LEVEL.powf(B));
+        let millis = a * level.powf(B) + c;
+        Duration::from_millis(millis as _)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The cells of rows drawn top first, '#' for a garbage mino, '=' for one of a row about to be
+    // cleared, anything else for a free cell
+    fn stack(rows: &[&str]) -> Vec<Mino> {
+        rows.iter()
+            .rev()
+            .flat_map(|line| {
+                line.chars().map(|cell| match cell {
+                    '#' => Mino::Garbage,
+                    '=' => Mino::PendingClear,
+                    _ => Mino::Free,
+                })
+            })
+            .collect()
+    }
+
+    // A game on a 10x20 stack with the given rows at the bottom
+    fn game(rows: &[&str]) -> Ttrys {
+        let mut ttrys = Ttrys::new(Options {
+            seed: Some(0),
+            ..Options::default()
+        });
+        let cells = stack(rows);
+        ttrys.stack[..cells.len()].copy_from_slice(&cells);
+        ttrys.update_heights();
+        ttrys
+    }
+
+    // Lock a piece with its minos on the given cells, then clear the rows it completed
+    fn lock(ttrys: &mut Ttrys, tetro: Tetromino, mut cells: [(i8, i8); 4]) {
+        cells.sort();
+        let mut state = RotationState::default();
+        for _ in 0..4 {
+            let mut minos = ttrys.minos(tetro, state);
+            minos.sort();
+            let offset = (cells[0].0 - minos[0].0, cells[0].1 - minos[0].1);
+            if minos.map(|(x, y)| (x + offset.0, y + offset.1)) == cells {
+                ttrys.cur_tetro = Some(tetro);
+                ttrys.cur_state = state;
+                ttrys.cur_position = offset;
+                ttrys.state = State::Lock;
+                ttrys.step();
+                if ttrys.state == State::ClearRows {
+                    ttrys.step();
+                }
+                return;
+            }
+            state = state.cw();
+        }
+        panic!("no orientation of {:?} covers {:?}", tetro, cells);
+    }
+
+    const I_COLUMN_9: [(i8, i8); 4] = [(9, 0), (9, 1), (9, 2), (9, 3)];
+
+    #[test]
+    fn tower_comes_down_with_the_cleared_rows() {
+        let mut ttrys = game(&[
+            "#.........",
+            "#.........",
+            "#.........",
+            "#.........",
+            "#.........",
+            "#.........",
+            "#########.",
+            "#########.",
+            "#########.",
+            "#########.",
+        ]);
+        assert_eq!(ttrys.stack_height(), 9);
+        lock(&mut ttrys, Tetromino::I, I_COLUMN_9);
+        assert_eq!(ttrys.column_heights(), &[6, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(ttrys.stack_height(), 5);
+        assert_eq!(ttrys.mino(0, 5), Mino::Garbage);
+        assert_eq!(ttrys.mino(0, 6), Mino::Free);
+    }
+
+    #[test]
+    fn rows_between_cleared_ones_drop() {
+        let mut ttrys = game(&["#########.", "####.####.", "#########."]);
+        lock(&mut ttrys, Tetromino::I, I_COLUMN_9);
+        assert_eq!(ttrys.column_heights(), &[1, 1, 1, 1, 0, 1, 1, 1, 1, 2]);
+        assert_eq!(ttrys.mino(4, 0), Mino::Free);
+        assert_eq!(ttrys.mino(9, 0), Mino::Occupied(Tetromino::I));
+        assert_eq!(ttrys.mino(9, 1), Mino::Occupied(Tetromino::I));
+    }
+
+    #[test]
+    fn clearing_the_whole_stack_empties_it() {
+        let mut ttrys = game(&["#########.", "#########.", "#########.", "#########."]);
+        lock(&mut ttrys, Tetromino::I, I_COLUMN_9);
+        assert_eq!(ttrys.column_heights(), &[0; 10]);
+        assert_eq!(ttrys.stack_height(), -1);
+        assert!(ttrys.stack.iter().all(|&mino| mino == Mino::Free));
+    }
+
+    #[test]
+    fn holes_count_in_the_column_heights() {
+        let mut ttrys = game(&["...#......", "..........", "#........."]);
+        assert_eq!(ttrys.column_heights(), &[1, 0, 0, 3, 0, 0, 0, 0, 0, 0]);
+        lock(&mut ttrys, Tetromino::O, [(5, 0), (6, 0), (5, 1), (6, 1)]);
+        assert_eq!(ttrys.column_heights(), &[1, 0, 0, 3, 0, 2, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn garbage_raises_the_columns() {
+        let mut ttrys = game(&[]);
+        ttrys.add_garbage(3);
+        ttrys.step();
+        // but for the column of the holes
+        let heights = ttrys.column_heights();
+        assert_eq!(heights.iter().filter(|&&height| height == 3).count(), 9);
+        assert_eq!(heights.iter().filter(|&&height| height == 0).count(), 1);
+    }
+
+    #[test]
+    fn no_full_row_leaves_the_stack_alone() {
+        let mut cells = stack(&["#.#", ".##", "##."]);
+        assert_eq!(clear_full_rows(&mut cells, 3), Vec::<usize>::new());
+        assert_eq!(cells, stack(&["#.#", ".##", "##."]));
+    }
+
+    #[test]
+    fn bottom_row_is_cleared() {
+        let mut cells = stack(&["...", "#..", ".#.", "###"]);
+        assert_eq!(clear_full_rows(&mut cells, 3), vec![0]);
+        assert_eq!(cells, stack(&["...", "...", "#..", ".#."]));
+    }
+
+    #[test]
+    fn top_row_is_cleared() {
+        let mut cells = stack(&["###", "#..", ".#."]);
+        assert_eq!(clear_full_rows(&mut cells, 3), vec![2]);
+        assert_eq!(cells, stack(&["...", "#..", ".#."]));
+    }
+
+    #[test]
+    fn rows_between_cleared_ones_come_down() {
+        let mut cells = stack(&["..#", "###", "#..", "###", "###", ".#.", "###"]);
+        assert_eq!(clear_full_rows(&mut cells, 3), vec![0, 2, 3, 5]);
+        assert_eq!(
+            cells,
+            stack(&["...", "...", "...", "...", "..#", "#..", ".#."])
+        );
+    }
+
+    #[test]
+    fn full_board_is_emptied() {
+        let mut cells = stack(&["###", "===", "###"]);
+        assert_eq!(clear_full_rows(&mut cells, 3), vec![0, 1, 2]);
+        assert_eq!(cells, stack(&["...", "...", "..."]));
+    }
+
+    #[test]
+    fn rows_about_to_be_cleared_are_full() {
+        let mut cells = stack(&["#=#", "==.", "==="]);
+        assert_eq!(clear_full_rows(&mut cells, 3), vec![0, 2]);
+        assert_eq!(cells, stack(&["...", "...", "==."]));
+    }
+
+    #[test]
+    fn clearing_the_top_rows_counts_their_lines() {
+        let mut ttrys = game(&["#########.", "#########.", "#########.", "#########."]);
+        lock(&mut ttrys, Tetromino::I, I_COLUMN_9);
+        assert_eq!(ttrys.score(), (4, 1000));
+    }
+
+    #[test]
+    fn apart_rows_are_scored_apart() {
+        let mut ttrys = game(&["#########.", "####.####.", "#########."]);
+        lock(&mut ttrys, Tetromino::I, I_COLUMN_9);
+        assert_eq!(ttrys.score(), (2, 200));
+    }
+
+    // A game with the given piece spawned on an empty stack
+    fn spawned(tetro: Tetromino) -> Ttrys {
+        let mut ttrys = game(&[]);
+        ttrys.spawn(tetro);
+        ttrys
+    }
+
+    #[test]
+    fn counterclockwise_rotations_go_counterclockwise() {
+        for tetro in [
+            Tetromino::I,
+            Tetromino::J,
+            Tetromino::L,
+            Tetromino::S,
+            Tetromino::T,
+        ] {
+            let mut ttrys = spawned(tetro);
+            let position = ttrys.cur_position;
+            let mut states = Vec::new();
+            for _ in 0..4 {
+                ttrys.update(UserAction::RotateCCW);
+                states.push(ttrys.cur_state.to_string());
+            }
+            assert_eq!(states, ["L", "2", "R", "0"], "{:?}", tetro);
+            assert_eq!(ttrys.cur_position, position, "{:?}", tetro);
+        }
+    }
+
+    #[test]
+    fn rotations_undo_each_other() {
+        let mut ttrys = spawned(Tetromino::T);
+        let position = ttrys.cur_position;
+        ttrys.update(UserAction::RotateCCW);
+        ttrys.update(UserAction::RotateCW);
+        assert_eq!(ttrys.cur_state.to_string(), "0");
+        assert_eq!(ttrys.cur_position, position);
+    }
+
+    #[test]
+    fn counterclockwise_rotations_kick_off_the_wall() {
+        for tetro in [
+            Tetromino::I,
+            Tetromino::J,
+            Tetromino::L,
+            Tetromino::S,
+            Tetromino::T,
+        ] {
+            let mut ttrys = spawned(tetro);
+            // stand the piece against the left wall
+            ttrys.update(UserAction::RotateCW);
+            for _ in 0..Options::MAX_SIZE {
+                ttrys.update(UserAction::MoveLeft);
+            }
+            for _ in 0..4 {
+                ttrys.update(UserAction::RotateCCW);
+                assert!(
+                    ttrys.events.contains(&GameEvent::PieceRotated),
+                    "{:?}",
+                    tetro
+                );
+            }
+            assert_eq!(ttrys.cur_state.to_string(), "R", "{:?}", tetro);
+            assert!(ttrys.piece().unwrap().1.iter().all(|&(col, _)| col >= 0));
+        }
+    }
+
+    #[test]
+    fn half_turns_flip_the_piece() {
+        for tetro in [
+            Tetromino::I,
+            Tetromino::J,
+            Tetromino::L,
+            Tetromino::S,
+            Tetromino::T,
+        ] {
+            let mut ttrys = spawned(tetro);
+            ttrys.update(UserAction::Rotate180);
+            assert_eq!(ttrys.cur_state.to_string(), "2", "{:?}", tetro);
+            ttrys.update(UserAction::RotateCW);
+            ttrys.update(UserAction::Rotate180);
+            assert_eq!(ttrys.cur_state.to_string(), "R", "{:?}", tetro);
+        }
+    }
+
+    #[test]
+    fn half_turns_kick_off_the_floor() {
+        let mut ttrys = spawned(Tetromino::T);
+        while !ttrys.collide(ttrys.cur_state, (0, -1)) {
+            ttrys.update(UserAction::SoftDrop);
+        }
+        ttrys.update(UserAction::Rotate180);
+        assert_eq!(ttrys.cur_state.to_string(), "2");
+        assert!(ttrys.piece().unwrap().1.iter().all(|&(_, row)| row >= 0));
+    }
+
+    #[test]
+    fn rotations_before_the_spawn_are_applied_to_the_piece() {
+        let mut ttrys = game(&[]);
+        ttrys.update(UserAction::RotateCW);
+        ttrys.update(UserAction::RotateCW);
+        ttrys.step();
+        assert_eq!(ttrys.cur_state.to_string(), "R");
+
+        // not to the next one
+        ttrys.update(UserAction::HardDrop);
+        ttrys.step();
+        ttrys.step();
+        ttrys.step();
+        assert_eq!(ttrys.cur_state.to_string(), "0");
+    }
+
+    #[test]
+    fn holds_before_the_spawn_are_applied_to_the_piece() {
+        let mut ttrys = game(&[]);
+        let first = ttrys.upcoming(2);
+        ttrys.update(UserAction::Hold);
+        ttrys.step();
+        assert_eq!(ttrys.hold(), Some(first[0]));
+        assert_eq!(ttrys.cur_tetro, Some(first[1]));
+        assert!(ttrys.hold_used());
+    }
+
+    #[test]
+    fn identical_games_hash_the_same() {
+        let mut first = game(&["#########.", "#########."]);
+        let mut second = game(&["#########.", "#########."]);
+        for ttrys in [&mut first, &mut second] {
+            ttrys.step();
+            ttrys.update(UserAction::RotateCW);
+            lock(ttrys, Tetromino::I, I_COLUMN_9);
+            ttrys.add_garbage(2);
+        }
+        assert_eq!(first.state_hash(), second.state_hash());
+    }
+
+    #[test]
+    fn diverging_games_hash_differently() {
+        let mut first = game(&[]);
+        let mut second = game(&[]);
+        first.step();
+        second.step();
+        first.update(UserAction::MoveLeft);
+        first.update(UserAction::HardDrop);
+        second.update(UserAction::HardDrop);
+        assert_ne!(first.state_hash(), second.state_hash());
+
+        // the same board, further in the game
+        let hash = game(&[]).state_hash();
+        let changes: [fn(&mut Ttrys); 4] = [
+            |ttrys| ttrys.score.1 += 100,
+            |ttrys| ttrys.level += 1,
+            |ttrys| ttrys.score.0 += 1,
+            |ttrys| ttrys.add_garbage(1),
+        ];
+        for change in changes {
+            let mut ttrys = game(&[]);
+            change(&mut ttrys);
+            assert_ne!(ttrys.state_hash(), hash);
+        }
+    }
+
+    #[test]
+    fn simulations_of_the_same_actions_end_the_same() {
+        let moves = [
+            UserAction::MoveLeft,
+            UserAction::RotateCW,
+            UserAction::MoveRight,
+            UserAction::MoveRight,
+            UserAction::SoftDrop,
+            UserAction::Hold,
+            UserAction::HardDrop,
+        ];
+        let actions: Vec<TimedAction> = (0..700)
+            .map(|idx| TimedAction {
+                at: Duration::from_millis(37 * idx as u64),
+                action: moves[idx % moves.len()],
+            })
+            .collect();
+        let until = Duration::from_secs(60);
+        let first = game(&[]).simulate(&actions, until);
+        let second = game(&[]).simulate(&actions, until);
+        assert!(!first.events.is_empty());
+        assert_eq!(first.state_hash, second.state_hash);
+        assert_eq!(first.score, second.score);
+        assert_eq!(first.lines, second.lines);
+        assert_eq!(first.events, second.events);
+    }
+
+    #[test]
+    fn simulations_go_on_with_the_gravity_alone() {
+        let summary = game(&[]).simulate(&[], Duration::from_secs(3600));
+        assert!(!summary.running);
+        assert!(summary.played > Duration::ZERO);
+        assert!(matches!(
+            summary.events.last(),
+            Some((_, GameEvent::GameOver { completed: false }))
+        ));
+
+        // or until the time is up
+        let summary = game(&[]).simulate(&[], Duration::from_secs(2));
+        assert!(summary.running);
+        assert!(summary.played <= Duration::from_secs(2));
+    }
+
+    #[test]
+    fn seven_bags_deal_each_tetromino_once_per_bag() {
+        let mut rng = ChaCha12Rng::seed_from_u64(0);
+        let mut sequence = TetrominoSequence::new(RandomizerKind::SevenBag, &mut rng);
+        for _ in 0..100 {
+            let mut bag: Vec<usize> = (0..7).map(|_| sequence.pop(&mut rng) as usize).collect();
+            bag.sort();
+            assert_eq!(bag, [0, 1, 2, 3, 4, 5, 6]);
+        }
+    }
+
+    #[test]
+    fn the_bot_plans_again_for_a_piece_locked_by_the_gravity() {
+        let mut ttrys = game(&[]);
+        let mut bot = bot::Bot::new();
+        while !ttrys.controllable() {
+            ttrys.step();
+        }
+        // start taking the first piece to its place, then let it fall
+        for _ in 0..2 {
+            let action = bot.next_action(&ttrys).unwrap();
+            ttrys.update(action);
+        }
+        while ttrys.stats().pieces() == 0 || !ttrys.controllable() {
+            ttrys.step();
+        }
+
+        // the next piece is played as by a bot seeing it first
+        let mut fresh = bot::Bot::new();
+        loop {
+            let action = bot.next_action(&ttrys).unwrap();
+            assert_eq!(Some(action), fresh.next_action(&ttrys));
+            ttrys.update(action);
+            if action == UserAction::HardDrop {
+                break;
+            }
+        }
+    }
+}
@@ -1,778 +1,2510 @@
-use core::fmt;
+mod audio;
+mod audit;
+mod bundle;
+mod canvas;
+mod config;
+mod latency;
+mod menu;
+mod netplay;
+mod notation;
+mod save;
+mod scores;
+mod theme;
+mod timeline;
+mod versus;
+
+use std::collections::VecDeque;
 use std::io::stdout;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use crossterm::event::KeyModifiers;
-use crossterm::style::Color;
-use crossterm::terminal::{BeginSynchronizedUpdate, EndSynchronizedUpdate};
+use crossterm::event::{
+    Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use crossterm::style::{Attribute, Color};
 use crossterm::{cursor, ExecutableCommand, QueueableCommand};
 
-use rand::seq::SliceRandom;
-
-use rand::{
-    distributions::{Distribution, Standard},
-    rngs::ThreadRng,
-    Rng,
+use ttrys::bot::Bot;
+use ttrys::rotation;
+use ttrys::{
+    AdaptiveGravity, ClearKind, GameEvent, GravityPolicy, Mino, Mode, Options, RandomizerKind,
+    Scoring, State, Tetromino, TetrominoSequence, Ttrys, UserAction,
 };
 
-const STACK_NUM_COLS: usize = 10;
-const STACK_NUM_ROWS: usize = 20;
-
-// One entry per tetromino, discribing each 4 rotation states by the relative position of the minos
-// The first state is the spawning state, and thes tates are listed clock-wise.
-const TETROMINO_DATA: [[[(i8, i8); 4]; 4]; 7] = [
-    // I
-    [
-        [(0, -1), (1, -1), (2, -1), (3, -1)],
-        [(2, 0), (2, -1), (2, -2), (2, -3)],
-        [(0, -2), (1, -2), (2, -2), (3, -2)],
-        [(1, 0), (1, -1), (1, -2), (1, -3)],
-    ],
-    // J
-    [
-        [(0, 0), (0, -1), (1, -1), (2, -1)],
-        [(1, 0), (2, 0), (1, -1), (1, -2)],
-        [(0, -1), (1, -1), (2, -1), (2, -2)],
-        [(1, 0), (1, -1), (0, -2), (1, -2)],
-    ],
-    // L
-    [
-        [(2, 0), (0, -1), (1, -1), (2, -1)],
-        [(1, 0), (1, -1), (1, -2), (2, -2)],
-        [(0, -1), (1, -1), (2, -1), (0, -2)],
-        [(0, 0), (1, 0), (1, -1), (1, -2)],
-    ],
-    // O
-    [[(1, 0), (2, 0), (1, -1), (2, -1)]; 4],
-    // S
-    [
-        [(1, 0), (2, 0), (0, -1), (1, -1)],
-        [(1, 0), (1, -1), (2, -1), (2, -2)],
-        [(1, -1), (2, -1), (0, -2), (1, -2)],
-        [(0, 0), (0, -1), (1, -1), (1, -2)],
-    ],
-    // T
-    [
-        [(1, 0), (0, -1), (1, -1), (2, -1)],
-        [(1, 0), (1, -1), (2, -1), (1, -2)],
-        [(0, -1), (1, -1), (2, -1), (1, -2)],
-        [(1, 0), (0, -1), (1, -1), (1, -2)],
-    ],
-    // Z
-    [
-        [(0, 0), (1, 0), (1, -1), (2, -1)],
-        [(2, 0), (1, -1), (2, -1), (1, -2)],
-        [(0, -1), (1, -1), (1, -2), (2, -2)],
-        [(1, 0), (0, -1), (1, -1), (0, -2)],
-    ],
-];
+use audio::Audio;
+use canvas::{Canvas, Screen};
+use config::{Config, Settings};
+use latency::LatencyMeter;
+use netplay::Board;
+use scores::{format_time, Entry, HighScores};
+use theme::{Palette, Theme};
+use timeline::Timeline;
+
+// Key bindings presets
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Layout {
+    // arrows under the right hand, hard drop under the left thumb
+    Default,
+    // everything under the right hand, around IJKL
+    OneHanded,
+    // the one-handed layout mirrored for the left hand, around WASD
+    OneHandedLeft,
+    // the two players of a versus game share the keyboard, pausing and quitting are common
+    VersusLeft,
+    VersusRight,
+}
 
-//#[derive(Clone, Copy, PartialEq)]
-//struct Color(u8);
+impl Layout {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Layout::Default),
+            "one-handed" => Some(Layout::OneHanded),
+            "one-handed-left" => Some(Layout::OneHandedLeft),
+            _ => None,
+        }
+    }
 
-#[derive(Clone, Copy, PartialEq)]
-enum Mino {
-    Free,
-    Occupied(Color),
-    PendingClear,
+    // Auto shift delay and repeat rate in ms, for the settings leaving the repeat to the terminal:
+    // the one-handed presets are played holding the move keys rather than tapping them, the hand
+    // having the rotations to do too
+    fn auto_shift(self) -> Option<(u16, u16)> {
+        match self {
+            Layout::OneHanded | Layout::OneHandedLeft => Some((120, 20)),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
-enum Tetromino {
-    I,
-    J,
-    L,
-    O,
-    S,
-    T,
-    Z,
+// Practice aid for ambidextrous stacking: the horizontal inputs and/or the board are mirrored
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Mirror {
+    None,
+    Input,
+    Board,
+    Both,
 }
 
-#[derive(Copy, Clone)]
-struct RotationState(u8);
+impl Mirror {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Mirror::None),
+            "input" => Some(Mirror::Input),
+            "board" => Some(Mirror::Board),
+            "both" => Some(Mirror::Both),
+            _ => None,
+        }
+    }
 
-impl RotationState {
-    fn cw(self) -> Self {
-        RotationState((self.0 + 1) % 4)
+    fn input(self) -> bool {
+        matches!(self, Mirror::Input | Mirror::Both)
     }
 
-    fn ccw(self) -> Self {
-        RotationState((self.0 + 3) % 4)
+    fn board(self) -> bool {
+        matches!(self, Mirror::Board | Mirror::Both)
     }
-}
 
-impl Default for RotationState {
-    fn default() -> Self {
-        Self(0)
+    // Left and right moves are swapped, and so are the rotations, which turn the other way in a
+    // mirror
+    fn apply(self, action: UserAction) -> UserAction {
+        if !self.input() {
+            return action;
+        }
+        match action {
+            UserAction::MoveLeft => UserAction::MoveRight,
+            UserAction::MoveRight => UserAction::MoveLeft,
+            UserAction::RotateCW => UserAction::RotateCCW,
+            UserAction::RotateCCW => UserAction::RotateCW,
+            action => action,
+        }
     }
 }
 
-impl From<RotationState> for usize {
-    fn from(value: RotationState) -> Self {
-        value.0 as usize
-    }
+struct KeyMap {
+    bindings: Vec<(crossterm::event::KeyCode, UserAction)>,
 }
 
-impl<T> From<T> for Tetromino
-where
-    T: std::borrow::Borrow<usize>,
-{
-    fn from(value: T) -> Self {
-        match *value.borrow() % 7 {
-            0 => Tetromino::I,
-            1 => Tetromino::J,
-            2 => Tetromino::L,
-            3 => Tetromino::O,
-            4 => Tetromino::S,
-            5 => Tetromino::T,
-            6 => Tetromino::Z,
-            _ => panic!("bad value"),
+impl KeyMap {
+    fn new(layout: Layout) -> Self {
+        use crossterm::event::KeyCode::*;
+        use crossterm::event::ModifierKeyCode;
+        use UserAction::*;
+
+        let bindings = match layout {
+            Layout::Default => vec![
+                (Left, MoveLeft),
+                (Right, MoveRight),
+                (Up, RotateCW),
+                (Char('z'), RotateCCW),
+                // reported by the terminals telling key releases only
+                (Modifier(ModifierKeyCode::LeftControl), RotateCCW),
+                (Modifier(ModifierKeyCode::RightControl), RotateCCW),
+                (Char('a'), Rotate180),
+                (Down, SoftDrop),
+                (Char(' '), HardDrop),
+                (Char('c'), Hold),
+                (Char('p'), TogglePause),
+                (Char('d'), ToggleDroughtCounter),
+                (F(1), ToggleHelp),
+                (Char('h'), ToggleHelp),
+                (Esc, Quit),
+                (Char('q'), Quit),
+                (Char('s'), Save),
+            ],
+            Layout::OneHanded => vec![
+                (Char('j'), MoveLeft),
+                (Char('l'), MoveRight),
+                (Char('i'), RotateCW),
+                (Char('k'), RotateCCW),
+                (Char(' '), HardDrop),
+                (Char('u'), Hold),
+                (Char('p'), TogglePause),
+                (Char('o'), ToggleDroughtCounter),
+                (F(1), ToggleHelp),
+                (Char('h'), ToggleHelp),
+                (Char('s'), Save),
+                (Esc, Quit),
+            ],
+            Layout::OneHandedLeft => vec![
+                (Char('a'), MoveLeft),
+                (Char('d'), MoveRight),
+                (Char('w'), RotateCW),
+                (Char('s'), RotateCCW),
+                (Char(' '), HardDrop),
+                (Char('r'), Hold),
+                (Char('q'), TogglePause),
+                (Char('e'), ToggleDroughtCounter),
+                (F(1), ToggleHelp),
+                (Char('h'), ToggleHelp),
+                (Char('f'), Save),
+                (Esc, Quit),
+            ],
+            Layout::VersusLeft => vec![
+                (Char('a'), MoveLeft),
+                (Char('d'), MoveRight),
+                (Char('w'), RotateCW),
+                (Char('s'), RotateCCW),
+                (Char(' '), HardDrop),
+                (Char('e'), Hold),
+                (Char('p'), TogglePause),
+                (Esc, Quit),
+            ],
+            Layout::VersusRight => vec![
+                (Left, MoveLeft),
+                (Right, MoveRight),
+                (Up, RotateCW),
+                (Down, RotateCCW),
+                (Enter, HardDrop),
+                (Char('/'), Hold),
+                (Char('p'), TogglePause),
+                (Esc, Quit),
+            ],
+        };
+        KeyMap { bindings }
+    }
+
+    fn action(&self, key: crossterm::event::KeyEvent) -> Option<UserAction> {
+        // Ctrl-C always quits, whatever the layout
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Some(UserAction::Quit);
+        }
+        // held keys are told apart from pressed ones by the auto shift alone
+        if key.kind == KeyEventKind::Release {
+            return None;
         }
+        let code = match key.code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            code => code,
+        };
+        self.bindings
+            .iter()
+            .find(|(binding, _)| *binding == code)
+            .map(|&(_, action)| action)
     }
 }
 
-impl fmt::Display for Tetromino {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let letters = "IJLOSTZ";
-        write!(f, "{}", letters.chars().nth(*self as usize).unwrap())
+struct RawModeGuard;
+
+impl RawModeGuard {
+    fn new() -> RawModeGuard {
+        use crossterm::terminal::enable_raw_mode;
+        enable_raw_mode().ok();
+        RawModeGuard
     }
 }
 
-impl Distribution<Tetromino> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Tetromino {
-        rng.gen::<usize>().into()
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        use crossterm::terminal::disable_raw_mode;
+        disable_raw_mode().ok();
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
-enum State {
-    Spawn,
-    Fall,
-    HardDrop,
-    Lock,
-    ClearRows,
-    Paused,
-    End,
-}
-
-struct Ttrys {
-    cur_tetro: Option<Tetromino>,
-    cur_position: (i8, i8),
-    cur_state: RotationState,
-    clear_rows: Vec<i8>,
-    score: (u32, u32), // line cleared / points
-    level: u32,
-    state: State,
-    saved_state: State,
-    stack: [Mino; STACK_NUM_COLS * STACK_NUM_ROWS],
-    stack_height: i8,
-    sequence: TetrominoSequence,
-}
-
-impl Ttrys {
-    fn new() -> Self {
-        Ttrys {
-            cur_tetro: None,
-            cur_position: (0, 0),
-            cur_state: RotationState::default(),
-            clear_rows: Vec::new(),
-            score: (0, 0),
-            level: 0,
-            state: State::Spawn,
-            saved_state: State::End,
-            stack: [Mino::Free; STACK_NUM_COLS * STACK_NUM_ROWS],
-            stack_height: 0,
-            sequence: TetrominoSequence::new(5),
-        }
-    }
-
-    #[allow(dead_code)]
-    fn random_fill(&mut self) {
-        for row in 0..STACK_NUM_ROWS {
-            let mut rng = ThreadRng::default();
-            for col in 0..STACK_NUM_COLS {
-                let brick = if rng.gen_bool(0.3) {
-                    let color = rng.gen();
-                    Mino::Occupied(Color::AnsiValue(color))
-                } else {
-                    Mino::Free
-                };
-                self.stack[row * STACK_NUM_COLS + col] = brick;
-            }
-        }
+// Takes over the terminal while the game is on: raw mode, alternate screen, hidden cursor, mouse
+// reports. All of it is undone when dropped, and on panic, before the message is printed.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> TerminalGuard {
+        use crossterm::event::EnableMouseCapture;
+        use crossterm::terminal::{enable_raw_mode, Clear, ClearType, EnterAlternateScreen};
+
+        static PANIC_HOOK: std::sync::Once = std::sync::Once::new();
+        PANIC_HOOK.call_once(|| {
+            let default_hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                restore_terminal();
+                default_hook(info);
+            }));
+        });
+
+        enable_raw_mode().ok();
+        TERMINAL_TAKEN.store(true, Ordering::Relaxed);
+        let mut stdout = stdout();
+        stdout.queue(EnterAlternateScreen).ok();
+        stdout.queue(Clear(ClearType::All)).ok();
+        stdout.queue(EnableMouseCapture).ok();
+        stdout.execute(cursor::Hide).ok();
+        TerminalGuard
     }
+}
 
-    fn clear_stack(&mut self) {
-        self.stack.fill(Mino::Free);
-        self.stack_height = 0;
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
     }
+}
 
-    // return whether to continue
-    fn step(&mut self) -> bool {
-        match self.state {
-            State::Spawn => {
-                self.cur_tetro = Some(self.sequence.pop());
-                self.cur_position = ((STACK_NUM_COLS as i8 / 2) - 2, (STACK_NUM_ROWS - 1) as i8);
-                if let Some(Tetromino::I) = self.cur_tetro {
-                    self.cur_position.1 += 1;
-                }
-                self.cur_state = RotationState::default();
-                self.state = if self.collide(self.cur_state, (0, 0)) {
-                    State::End
-                } else {
-                    State::Fall
-                };
-            }
-            State::HardDrop => {
-                let mut offset = -1;
-                while !self.collide(self.cur_state, (0, offset)) {
-                    offset -= 1;
-                }
-                self.cur_position.1 += offset + 1;
-                self.state = State::Lock;
-            }
-            State::Fall => {
-                if self.collide(self.cur_state, (0, -1)) {
-                    self.state = State::Lock;
-                } else {
-                    self.cur_position.1 -= 1;
-                }
+// Shortest time between two redraws
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+// Time between two actions of the bot in demo games
+const DEMO_INTERVAL: Duration = Duration::from_millis(80);
+// Title drawn over the demo games of the idle menu, in letters of 3 by 5 blocks
+const TITLE: [&str; 5] = [
+    "### ### ##  # #  ##",
+    " #   #  # # # # #  ",
+    " #   #  ##   #   # ",
+    " #   #  # #  #    #",
+    " #   #  # #  #  ## ",
+];
+// Shortest time between two redraws in power saving mode
+const POWERSAVE_FRAME_INTERVAL: Duration = Duration::from_millis(100);
+// Highest frame rate --fps accepts
+const MAX_FPS: u32 = 240;
+
+// Whether the computer runs on battery, as far as Linux tells
+fn on_battery() -> bool {
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    supplies.flatten().any(|supply| {
+        let read = |name: &str| std::fs::read_to_string(supply.path().join(name));
+        read("type").is_ok_and(|kind| kind.trim() == "Battery")
+            && read("status").is_ok_and(|status| status.trim() == "Discharging")
+    })
+}
+
+// Whether the locale tells of UTF-8 text, as far as the environment variables go: without it, the
+// box drawing characters would come out garbled. The first variable set wins, as for setlocale().
+fn utf8_locale() -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+        .to_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+// Read the terminal events on a thread of their own, so that waiting for a key never holds the
+// game back. Each event comes with the time it was received.
+fn spawn_input() -> Receiver<(Instant, Event)> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = crossterm::event::read() {
+            if sender.send((Instant::now(), event)).is_err() {
+                break;
             }
-            State::Lock => {
-                let idx: usize = self.cur_tetro.unwrap() as usize;
-                let state: usize = self.cur_state.into();
+        }
+    });
+    receiver
+}
 
-                // finalize the locked piece into the stack
-                TETROMINO_DATA[idx][state]
-                    .iter()
-                    .map(|(x, y)| {
-                        let h = self.cur_position.1 + y;
-                        let idx = h as usize * STACK_NUM_COLS + (self.cur_position.0 + x) as usize;
-                        self.stack_height = self.stack_height.max(h);
-                        idx
-                    })
-                    .for_each(|idx| {
-                        let block = &mut self.stack[idx];
-                        *block = Mino::Occupied(tetro_color(self.cur_tetro.unwrap()));
-                    });
-
-                // list the full rows after locking the tetromino into the stack
-                // for later removal
-                let set: std::collections::HashSet<i8> = TETROMINO_DATA[idx][state]
-                    .iter()
-                    .map(|(_, offset)| self.cur_position.1 + offset)
-                    .collect();
-                self.clear_rows = set
-                    .into_iter()
-                    .filter(|&row| {
-                        let start = (row as usize) * STACK_NUM_COLS;
-                        let end = start + STACK_NUM_COLS;
-                        self.stack[start..end]
-                            .iter()
-                            .fold(true, |full, block| full && *block != Mino::Free)
-                    })
-                    .collect();
-                self.clear_rows.sort();
-                if !self.clear_rows.is_empty() {
-                    // Color full rows in a special way
-                    self.clear_rows.iter().for_each(|&row| {
-                        let start = (row as usize) * STACK_NUM_COLS;
-                        let end = start + STACK_NUM_COLS;
-                        self.stack[start..end]
-                            .iter_mut()
-                            .for_each(|block| *block = Mino::PendingClear)
-                    });
-                    self.state = State::ClearRows;
-                } else {
-                    self.state = State::Spawn;
-                }
-            }
-            State::ClearRows => {
-                // Drop rows down where cleared rows have left space.
-                // gather the clear streaks (set of consecutives lines cleared) for later scoring
-                let mut clear_streaks = Vec::new();
-                // use the stack height as convenient sentinel
-                self.clear_rows.push(self.stack_height + 1);
-
-                let mut clear_it = self.clear_rows.iter();
-                let mut clear_row = *clear_it.next().unwrap();
-                let mut drop = 0;
-                let mut streak = 0;
-                for row in 0..=self.stack_height {
-                    if row < clear_row {
-                        if drop > 0 {
-                            let src_blocks = (row as usize * STACK_NUM_COLS)
-                                ..((row as usize + 1) * STACK_NUM_COLS);
-                            let dst = (row - drop) as usize * STACK_NUM_COLS;
-                            self.stack.copy_within(src_blocks, dst);
-                            if streak > 0 {
-                                clear_streaks.push(streak);
-                                streak = 0;
-                            }
-                        }
-                    } else {
-                        clear_row = *clear_it.next().unwrap();
-                        drop += 1;
-                        streak += 1;
-                    }
-                }
-                // the top rows now contains gabarge, clear them
-                for r in 0..drop {
-                    let h = self.stack_height;
-                    let row_range = ((h - r) as usize * STACK_NUM_COLS)
-                        ..((h - r + 1) as usize * STACK_NUM_COLS);
-                    self.stack[row_range].fill(Mino::Free);
-                }
-                self.stack_height -= self.clear_rows.len() as i8 - 1;
-                self.clear_rows.clear();
+struct Timeout {
+    start: Instant,
+    duration: Duration,
+    // time left when paused
+    paused: Option<Duration>,
+}
+
+impl Timeout {
+    fn new(duration: Duration) -> Self {
+        Timeout {
+            start: Instant::now(),
+            duration,
+            paused: None,
+        }
+    }
 
-                self.cur_tetro = None;
+    fn remaining(&self) -> Duration {
+        if let Some(remaining) = self.paused {
+            remaining
+        } else if self.expired() {
+            Duration::default()
+        } else {
+            self.duration - self.start.elapsed()
+        }
+    }
 
-                self.state = State::Spawn;
+    fn expired(&self) -> bool {
+        self.paused.is_none() && self.start.elapsed() > self.duration
+    }
 
-                // update score
-                for streak in clear_streaks {
-                    self.score.1 += self.clear_reward(streak);
-                    self.score.0 += streak as u32;
-                }
-                self.level = self.score.1 / 1000;
-            }
-            _ => (),
+    fn pause(&mut self) {
+        if self.paused.is_none() {
+            self.paused = Some(self.remaining());
         }
-        false
     }
 
-    fn collide(&self, rotation: RotationState, offset: (i8, i8)) -> bool {
-        if let Some(tetro) = self.cur_tetro {
-            let idx = tetro as usize;
-            let minos: [(i8, i8); 4] =
-                TETROMINO_DATA[idx][<RotationState as Into<usize>>::into(rotation)];
-            let x0 = self.cur_position.0 + offset.0;
-            let y0 = self.cur_position.1 + offset.1;
-            for mino in minos {
-                let x = x0 + mino.0;
-                let y = y0 + mino.1;
-                if (0..STACK_NUM_COLS as i8).contains(&x) && (0..STACK_NUM_ROWS as i8).contains(&y)
-                {
-                    if self.stack[y as usize * STACK_NUM_COLS + x as usize] != Mino::Free {
-                        return true;
-                    }
-                } else {
-                    return true;
-                }
-            }
+    fn resume(&mut self) {
+        if let Some(remaining) = self.paused.take() {
+            self.start = Instant::now();
+            self.duration = remaining;
         }
-        false
     }
+}
 
-    // Return potential wall kick offset
-    fn test_rotation(&self, cw: bool) -> Option<(i8, i8)> {
-        const WALL_KICK_OFFSETS: [[(i8, i8); 4]; 8] = [[(0, 0); 4]; 8];
-        let (direction, next_state) = if cw {
-            (0, self.cur_state.cw())
-        } else {
-            (1, self.cur_state.ccw())
+// Delayed auto shift: a held move key moves the piece once, again after the delay, then at the
+// repeat rate until released. Telling held keys apart takes a terminal reporting key releases
+// (kitty keyboard protocol); elsewhere, or with the delay off, keys repeat as the terminal does.
+struct AutoShift {
+    delay: Option<Duration>,
+    rate: Duration,
+    // the key held, its move and when it next repeats, never once a zero rate reached the wall
+    held: Option<(KeyCode, UserAction, Option<Instant>)>,
+}
+
+impl AutoShift {
+    fn new(settings: &Settings, layout: Layout, key_releases: bool) -> Self {
+        let (das, arr) = match (settings.das, layout.auto_shift()) {
+            (None, Some((das, arr))) => (Some(das), arr),
+            (das, _) => (das, settings.arr),
         };
-        let rotation_id: usize = self.cur_state.into();
-        for offset in WALL_KICK_OFFSETS[2 * rotation_id + direction] {
-            if !self.collide(next_state, offset) {
-                return Some(offset);
-            }
+        AutoShift {
+            delay: das
+                .filter(|_| key_releases)
+                .map(|das| Duration::from_millis(das.into())),
+            rate: Duration::from_millis(arr.into()),
+            held: None,
         }
-        None
     }
 
-    fn update(&mut self, action: UserAction) {
-        match action {
-            UserAction::MoveLeft => {
-                if self.state != State::Fall {
-                    return;
-                }
-                if !self.collide(self.cur_state, (-1, 0)) {
-                    self.cur_position.0 = self.cur_position.0.saturating_sub(1);
-                }
-            }
-            UserAction::MoveRight => {
-                if self.state != State::Fall {
-                    return;
-                }
-                if !self.collide(self.cur_state, (1, 0)) {
-                    self.cur_position.0 += 1;
-                }
-            }
-            UserAction::RotateCW | UserAction::RotateCCW => {
-                if self.state != State::Fall {
-                    return;
-                }
-                if let Some(offset) = self.test_rotation(action == UserAction::RotateCW) {
-                    self.cur_state = self.cur_state.cw();
-                    self.cur_position.0 += offset.0;
-                    self.cur_position.1 += offset.1;
-                }
-            }
-            UserAction::HardDrop => {
-                if self.state == State::Fall {
-                    self.state = State::HardDrop;
-                }
-            }
-            UserAction::Quit => {
-                self.state = State::End;
-            }
-            UserAction::TogglePause => {
-                if self.state == State::Paused {
-                    self.state = self.saved_state;
-                } else {
-                    self.saved_state = self.state;
-                    self.state = State::Paused;
-                }
-            }
-            UserAction::ClearStack => self.clear_stack(),
-            //_ => (),
+    fn enabled(&self) -> bool {
+        self.delay.is_some()
+    }
+
+    fn press(&mut self, code: KeyCode, action: UserAction, time: Instant) {
+        if let Some(delay) = self.delay {
+            self.held = Some((code, action, Some(time + delay)));
         }
     }
 
-    fn level(&self) -> u32 {
-        self.level
+    fn release(&mut self, code: KeyCode) {
+        if self.held.is_some_and(|(held, _, _)| held == code) {
+            self.held = None;
+        }
     }
 
-    fn score(&self) -> (u32, u32) {
-        self.score
+    fn release_all(&mut self) {
+        self.held = None;
     }
 
-    fn clear_reward(&self, combo_size: i8) -> u32 {
-        let rewards = [100, 250, 500, 1000];
-        rewards[(combo_size - 1).clamp(0, 3) as usize]
+    // While there is no piece to move, the key keeps charging: a repeat falling due waits for the
+    // next piece, which moves as soon as it spawns
+    fn charge(&mut self, now: Instant) {
+        if let Some((_, _, Some(next))) = self.held.as_mut() {
+            *next = (*next).max(now);
+        }
     }
 
-    fn running(&self) -> bool {
-        self.state != State::End
+    // When the next move is due
+    fn due(&self) -> Option<Instant> {
+        self.held.and_then(|(_, _, next)| next)
     }
-}
 
-struct TetrominoSequence {
-    cur_tetro: Tetromino,
-    bag: Vec<Tetromino>,
-    bag_size: usize,
+    // The move to repeat and how many times, once due
+    fn repeat(&mut self, now: Instant) -> Option<(UserAction, usize)> {
+        let (_, action, next) = self.held.as_mut()?;
+        let due = next.filter(|&due| due <= now)?;
+        if self.rate.is_zero() {
+            *next = None;
+            return Some((*action, Options::MAX_SIZE));
+        }
+        let count = 1 + ((now - due).as_nanos() / self.rate.as_nanos()) as usize;
+        *next = Some(due + self.rate * count as u32);
+        Some((*action, count))
+    }
 }
 
-impl TetrominoSequence {
-    fn new(bag_size: usize) -> Self {
-        let bag_size = bag_size.max(1).min(7);
-        let mut this = TetrominoSequence {
-            cur_tetro: Tetromino::I,
-            bag: Vec::with_capacity(bag_size),
-            bag_size,
-        };
-        this.pop();
-        this
+// The messages telling of the notable events: T-spins, tetrises, back-to-back clears and level ups
+fn toasts(events: &[GameEvent]) -> Vec<String> {
+    let mut toasts = Vec::new();
+    let mut spin = None;
+    for event in events {
+        match *event {
+            GameEvent::TSpin { rows, mini } => spin = Some((rows, mini)),
+            GameEvent::LinesCleared {
+                kind, back_to_back, ..
+            } => {
+                if spin.is_none() && kind == ClearKind::Tetris {
+                    toasts.push(String::from("TETRIS!"));
+                }
+                if back_to_back {
+                    toasts.push(String::from("BACK-TO-BACK"));
+                }
+            }
+            GameEvent::LevelUp { level } => toasts.push(format!("LEVEL UP ({})", level)),
+            _ => (),
+        }
     }
-
-    fn peek(&self) -> Tetromino {
-        self.cur_tetro
+    // the T-spin comes first, its clear is named after it
+    if let Some((rows, mini)) = spin {
+        let lines = ["", " SINGLE", " DOUBLE", " TRIPLE"];
+        let mini = if mini { "MINI " } else { "" };
+        toasts.insert(0, format!("{}T-SPIN{}", mini, lines[rows.min(3)]));
     }
+    toasts
+}
 
-    fn pop(&mut self) -> Tetromino {
-        if self.bag.is_empty() {
-            let mut ids: [usize; 7] = core::array::from_fn(|i| (i + 1) as usize);
-            ids.partial_shuffle(&mut ThreadRng::default(), self.bag_size)
-                .0
-                .iter()
-                .for_each(|idx| self.bag.push(idx.into()));
+// Mouse play: a click in a column of the stack takes the leftmost mino of the piece there, the
+// wheel rotates the piece and a right click hard-drops it
+fn mouse_actions(mouse: MouseEvent, ttrys: &Ttrys, display: &GameScreen) -> Vec<UserAction> {
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            let target = display.board_column(ttrys, mouse.column, mouse.row);
+            let (Some(target), Some((_, minos))) = (target, ttrys.piece()) else {
+                return Vec::new();
+            };
+            let left = minos.iter().map(|mino| mino.0).min().unwrap_or(0);
+            let moves = target as i8 - left;
+            let action = if moves < 0 {
+                UserAction::MoveLeft
+            } else {
+                UserAction::MoveRight
+            };
+            vec![action; moves.unsigned_abs() as usize]
         }
-        let ret = self.cur_tetro;
-        self.cur_tetro = self.bag.pop().unwrap();
-        ret
+        MouseEventKind::Down(MouseButton::Right) => vec![UserAction::HardDrop],
+        MouseEventKind::ScrollUp => vec![UserAction::RotateCW],
+        MouseEventKind::ScrollDown => vec![UserAction::RotateCCW],
+        _ => Vec::new(),
     }
 }
 
-#[derive(PartialEq, Debug)]
-enum UserAction {
-    MoveLeft,
-    MoveRight,
-    RotateCW,
-    RotateCCW,
-    HardDrop,
-    //SoftDrop,
-    TogglePause,
-    ClearStack, // hack
-    Quit,
+// The title over the demo games of the idle menu, in full blocks unless drawing in ASCII
+fn title_overlay(style: RenderStyle) -> Vec<(String, Attribute)> {
+    let block = if style == RenderStyle::Ascii {
+        "#"
+    } else {
+        "█"
+    };
+    let mut lines: Vec<(String, Attribute)> = TITLE
+        .iter()
+        .map(|line| (line.replace('#', block), Attribute::Bold))
+        .collect();
+    lines.push((String::new(), Attribute::Reset));
+    lines.push((String::from("press any key"), Attribute::Dim));
+    lines
 }
 
-struct RawModeGuard;
+// Entry delay of the games, as set in the settings
+fn spawn_delay(settings: &Settings) -> Option<Duration> {
+    settings
+        .spawn_delay
+        .map(|delay| Duration::from_millis(delay.into()))
+}
 
-impl RawModeGuard {
-    fn new() -> RawModeGuard {
-        use crossterm::terminal::enable_raw_mode;
-        enable_raw_mode().ok();
-        RawModeGuard
+static KEY_RELEASES: AtomicBool = AtomicBool::new(false);
+
+// Have the terminal report key releases, for the auto shift, or stop it
+fn report_key_releases(on: bool) {
+    use crossterm::event::{
+        KeyboardEnhancementFlags as Flags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    };
+
+    if KEY_RELEASES.swap(on, Ordering::Relaxed) == on {
+        return;
+    }
+    let mut stdout = stdout();
+    if on {
+        let flags = Flags::DISAMBIGUATE_ESCAPE_CODES
+            | Flags::REPORT_EVENT_TYPES
+            | Flags::REPORT_ALTERNATE_KEYS
+            | Flags::REPORT_ALL_KEYS_AS_ESCAPE_CODES;
+        stdout.execute(PushKeyboardEnhancementFlags(flags)).ok();
+    } else {
+        stdout.execute(PopKeyboardEnhancementFlags).ok();
     }
 }
 
-impl Drop for RawModeGuard {
-    fn drop(&mut self) {
-        use crossterm::terminal::disable_raw_mode;
-        disable_raw_mode().ok();
+// How the minos are drawn
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum RenderStyle {
+    // two spaces on a colored background
+    DoubleSpace,
+    // half block characters, two cells one above the other: square minos on more fonts, on a
+    // board half as tall
+    HalfBlock,
+    // "[]" in the color of the piece, for terminals with poor support of background colors
+    Ascii,
+}
+
+impl RenderStyle {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "double-space" => Some(RenderStyle::DoubleSpace),
+            "half-block" => Some(RenderStyle::HalfBlock),
+            "ascii" => Some(RenderStyle::Ascii),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            RenderStyle::DoubleSpace => "double-space",
+            RenderStyle::HalfBlock => "half-block",
+            RenderStyle::Ascii => "ascii",
+        }
     }
 }
 
-fn get_user_action(timeout: &Timeout) -> Option<UserAction> {
-    use crossterm::event::{poll, read, Event, KeyCode};
+// What a cell of the stack shows, drawn according to the render style
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum CellLook {
+    Empty,
+    // a mino, or a flashing one
+    Solid(Color),
+    Ghost(Color),
+    // a mino of an invisible game about to disappear
+    Fading(Color),
+    // a hard drop went through, and whether the trail is fading
+    Trail(Color, bool),
+    // a row being cleared, lit in the given color
+    Clearing(Option<Color>),
+}
 
-    let _raw_mode = RawModeGuard::new();
+// How the game screen trades looks for responsiveness
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum RenderProfile {
+    Standard,
+    // for slow links (SSH): no animations
+    Competitive,
+}
 
-    poll(timeout.remaining()).map_or(None, |has_event| {
-        if has_event {
-            read().map_or(None, |event| match event {
-                Event::Key(key_event) => match key_event.code {
-                    KeyCode::Left => Some(UserAction::MoveLeft),
-                    KeyCode::Right => Some(UserAction::MoveRight),
-                    KeyCode::Up => Some(UserAction::RotateCW),
-                    KeyCode::Down => Some(UserAction::RotateCCW),
-                    KeyCode::Char(' ') => Some(UserAction::HardDrop),
-                    KeyCode::Char('p') => Some(UserAction::TogglePause),
-                    KeyCode::Char('x') => Some(UserAction::ClearStack),
-                    KeyCode::Esc | KeyCode::Char('q') => Some(UserAction::Quit),
-                    KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                        Some(UserAction::Quit)
-                    }
-                    _ => None,
-                },
-                _ => None,
-            })
-        } else {
-            None
+impl RenderProfile {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(RenderProfile::Standard),
+            "competitive" => Some(RenderProfile::Competitive),
+            _ => None,
         }
-    })
+    }
 }
 
-struct Timeout {
-    start: Instant,
-    duration: Duration,
+struct GameScreen {
+    theme: Theme,
+    // cells sent to the terminal, and the next frame
+    screen: Screen,
+    // column of the left edge, boards are drawn side by side in versus games
+    origin: u16,
+    // number of upcoming pieces shown
+    preview_count: usize,
+    // number of free rows at the top of the stack under which the danger warning kicks in
+    danger_margin: usize,
+    mirrored: bool,
+    show_droughts: bool,
+    // the keys of the layout and their actions, listed beside the HUD when shown
+    help: Vec<String>,
+    show_help: bool,
+    style: RenderStyle,
+    ghost: bool,
+    // the terminal bell rung when the stack gets high
+    sound: bool,
+    // whether the pause tells about the settings screen
+    pause_settings: bool,
+    // diagnostics line, drawn right of the bottom border
+    diagnostics: Option<String>,
+    // accessibility profile: no flashing effects, informational cues are kept
+    reduced_motion: bool,
+    in_danger: bool,
+    // when the rows being cleared were first drawn
+    clear_started: Option<Instant>,
+    // hard drop trails and lock flashes being shown, with their start time
+    effects: Vec<(Instant, GameEvent)>,
+    // cells of the stack locked lately, with their lock time: in invisible games, only those are
+    // shown, until they fade out
+    locked_cells: Vec<((i8, i8), Instant)>,
+    // messages telling of the feats of the last pieces, with their start time, shown under the
+    // score until they fade out
+    toasts: Vec<(Instant, String)>,
+    // lines drawn over the board with their attribute, the game showing through their blanks
+    overlay: Vec<(String, Attribute)>,
+    // frames drawn so far
+    frame: u64,
+    // average time taken to draw a frame, and whether it is too long for the effects
+    draw_time: Duration,
+    degraded: bool,
+    created: Instant,
 }
 
-impl Timeout {
-    fn new(duration: Duration) -> Self {
-        Timeout {
-            start: Instant::now(),
-            duration,
+impl GameScreen {
+    // pieces without an I after which the drought counter is highlighted
+    const DROUGHT_WARNING: u32 = 12;
+    const DANGER_COLOR: Color = Color::Red;
+    // the rows being cleared are lit and blanked in turn for this long
+    const CLEAR_FLASH_PERIOD: Duration = Duration::from_millis(50);
+    // how long the trail of a hard drop, and the flash of a locked piece last
+    const TRAIL_DURATION: Duration = Duration::from_millis(150);
+    const LOCK_FLASH_DURATION: Duration = Duration::from_millis(100);
+    // in invisible games, how long the locked minos stay visible, the second half fading
+    const INVISIBLE_DELAY: Duration = Duration::from_millis(1500);
+    // how long the toasts stay, the second half fading
+    const TOAST_DURATION: Duration = Duration::from_millis(1500);
+    // columns the toasts may take, blanked when they are gone
+    const TOAST_WIDTH: u16 = 40;
+    // the ghost piece drawn with half blocks, where it can't be an outline
+    const HALF_BLOCK_GHOST: Color = Color::AnsiValue(239);
+    // the HUD is drawn every that many frames when drawing is late
+    const DEGRADED_HUD_INTERVAL: u64 = 4;
+    const DEFAULT_PREVIEW_COUNT: usize = 3;
+    const DEFAULT_DANGER_MARGIN: usize = 5;
+    // columns left of the stack, where the held piece is drawn
+    const PADDING_LEFT: u16 = 14;
+    // columns right of the stack, where the previews and counters are drawn
+    const PADDING_RIGHT: u16 = 40;
+    // columns taken by the key help, right of the HUD
+    const HELP_WIDTH: u16 = 28;
+    // screen row of the top border of the stack
+    const TOP_ROW: u16 = 0;
+
+    fn new(theme: Theme, reduced_motion: bool, profile: RenderProfile) -> Self {
+        GameScreen {
+            theme,
+            screen: Screen::default(),
+            origin: 0,
+            preview_count: Self::DEFAULT_PREVIEW_COUNT,
+            danger_margin: Self::DEFAULT_DANGER_MARGIN,
+            mirrored: false,
+            show_droughts: false,
+            help: Vec::new(),
+            show_help: false,
+            style: RenderStyle::DoubleSpace,
+            ghost: true,
+            sound: true,
+            pause_settings: false,
+            diagnostics: None,
+            reduced_motion: reduced_motion || profile == RenderProfile::Competitive,
+            in_danger: false,
+            clear_started: None,
+            effects: Vec::new(),
+            locked_cells: Vec::new(),
+            toasts: Vec::new(),
+            overlay: Vec::new(),
+            frame: 0,
+            draw_time: Duration::ZERO,
+            degraded: false,
+            created: Instant::now(),
         }
     }
 
-    fn remaining(&self) -> Duration {
-        if self.expired() {
-            Duration::default()
-        } else {
-            self.duration - self.start.elapsed()
-        }
+    // Heartbeat pattern for the danger warning: two short beats followed by a rest
+    fn heartbeat(&self) -> bool {
+        let t = self.created.elapsed().as_millis() % 1200;
+        t < 120 || (240..360).contains(&t)
     }
 
-    fn expired(&self) -> bool {
-        self.start.elapsed() > self.duration
+    // Number of columns taken by the board of a game and everything around it
+    fn columns(ttrys: &Ttrys, style: RenderStyle) -> u16 {
+        Self::PADDING_LEFT + 2 + Self::board_size(ttrys, style).0 + Self::PADDING_RIGHT
     }
-}
 
-fn tetro_color(tetro: Tetromino) -> Color {
-    match tetro {
-        Tetromino::I => Color::Cyan,
-        Tetromino::J => Color::Blue,
-        Tetromino::L => Color::AnsiValue(214),
-        Tetromino::O => Color::Yellow,
-        Tetromino::S => Color::Green,
-        Tetromino::T => Color::Magenta,
-        Tetromino::Z => Color::Red,
+    // Columns and rows of the screen taken by the inside of the stack
+    fn board_size(ttrys: &Ttrys, style: RenderStyle) -> (u16, u16) {
+        let (num_cols, num_rows) = (ttrys.width() as u16, ttrys.height() as u16);
+        match style {
+            RenderStyle::DoubleSpace | RenderStyle::Ascii => (2 * num_cols, num_rows),
+            RenderStyle::HalfBlock => (num_cols, num_rows.div_ceil(2)),
+        }
     }
-}
 
-struct GameScreen;
+    // Corners, horizontal and vertical lines of the frames: top left, horizontal, top right,
+    // vertical, bottom left, bottom right
+    fn frame_chars(&self) -> [&'static str; 6] {
+        match self.style {
+            RenderStyle::DoubleSpace | RenderStyle::HalfBlock => ["╔", "═", "╗", "║", "╚", "╝"],
+            RenderStyle::Ascii => ["+", "-", "+", "|", "+", "+"],
+        }
+    }
 
-impl GameScreen {
-    fn new() -> Self {
-        let mut stdout = stdout();
-        stdout.queue(cursor::Hide).ok();
-        GameScreen
+    // Top line of a frame `width` columns wide inside, with a title in the middle
+    fn frame_top(&self, title: &str, width: u16) -> String {
+        let frame = self.frame_chars();
+        let padding = (width as usize).saturating_sub(title.chars().count());
+        format!(
+            "{}{}{}{}{}",
+            frame[0],
+            frame[1].repeat(padding / 2),
+            title,
+            frame[1].repeat(padding - padding / 2),
+            frame[2]
+        )
     }
 
-    fn draw(&self, ttrys: &Ttrys) -> crossterm::Result<std::io::Stdout> {
-        use crossterm::style;
-        use std::io::Write;
+    fn frame_bottom(&self, width: u16) -> String {
+        let frame = self.frame_chars();
+        format!(
+            "{}{}{}",
+            frame[4],
+            frame[1].repeat(width as usize),
+            frame[5]
+        )
+    }
 
-        let padding_left = 5;
+    // Foreground, background and text of a cell two columns wide
+    fn cell_text(&self, look: CellLook) -> (Option<Color>, Option<Color>, &'static str) {
+        let ascii = self.style == RenderStyle::Ascii;
+        match look {
+            CellLook::Empty => (None, None, "  "),
+            CellLook::Solid(color) if ascii => (Some(color), None, "[]"),
+            CellLook::Solid(color) => (None, Some(color), "  "),
+            CellLook::Ghost(color) if ascii => (Some(color), None, "::"),
+            CellLook::Ghost(color) | CellLook::Fading(color) => (Some(color), None, "[]"),
+            CellLook::Trail(color, faded) => {
+                (Some(color), None, if faded || ascii { ".." } else { "::" })
+            }
+            CellLook::Clearing(Some(color)) if ascii => (Some(color), None, "<>"),
+            CellLook::Clearing(color) => (None, color, "<>"),
+        }
+    }
 
-        let mut s = stdout();
+    // Color of a cell drawn as half a character, None for the background. The ghost piece gets
+    // a color of its own, the trails are left out.
+    fn cell_fill(&self, look: CellLook) -> Option<Color> {
+        match look {
+            CellLook::Empty | CellLook::Trail(..) => None,
+            CellLook::Solid(color) | CellLook::Fading(color) => Some(color),
+            CellLook::Ghost(_) => Some(Self::HALF_BLOCK_GHOST),
+            CellLook::Clearing(color) => Some(color.unwrap_or(self.theme.clear_color())),
+        }
+    }
 
-        s.execute(BeginSynchronizedUpdate)?;
+    // Two cells, one above the other, in a character
+    fn print_half_block(&self, canvas: &mut Canvas, top: Option<Color>, bottom: Option<Color>) {
+        let background = self.theme.background_color();
+        match (top, bottom) {
+            (Some(top), bottom) => {
+                canvas.set_foreground(top);
+                canvas.set_background(bottom.unwrap_or(background));
+                canvas.print("▀");
+            }
+            (None, Some(bottom)) => {
+                canvas.set_foreground(bottom);
+                canvas.set_background(background);
+                canvas.print("▄");
+            }
+            (None, None) => {
+                canvas.set_background(background);
+                canvas.print(" ");
+            }
+        }
+        canvas.reset_color();
+    }
 
-        // stack top
-        s.queue(cursor::MoveToColumn(padding_left))?;
-        s.queue(style::Print("╔"))?;
-        let horiz_border = "═".repeat(2);
-        for _ in 0..STACK_NUM_COLS {
-            s.queue(style::Print(&horiz_border))?;
+    // A cell two columns wide, on `background` unless it has a color of its own. The minos are
+    // shaded on their right half when the theme can.
+    fn print_cell(&self, canvas: &mut Canvas, look: CellLook, background: Color) {
+        let (fg, bg, text) = self.cell_text(look);
+        let shade = match look {
+            CellLook::Solid(color) if self.style == RenderStyle::DoubleSpace => {
+                self.theme.shade(color)
+            }
+            _ => None,
+        };
+        if let Some(fg) = fg {
+            canvas.set_foreground(fg);
+        }
+        canvas.set_background(bg.unwrap_or(background));
+        match shade {
+            Some(shade) => {
+                canvas.print(" ");
+                canvas.set_background(shade);
+                canvas.print(" ");
+            }
+            None => canvas.print(text),
         }
-        s.queue(style::Print("╗\n"))?;
+        canvas.reset_color();
+    }
 
-        // Draw the stack content, including the current piece. This way there is
-        // no intermediate state with the piece blanked out. Alternative would be
-        // to rasterize the piece in a copy of a the stack.
-        let mut tetro_coord = (0, 0); // coordinates of a grid block piece local frame
-        for row in (0..STACK_NUM_ROWS).rev() {
-            s.queue(cursor::MoveToColumn(padding_left))?;
-            s.queue(style::Print("║"))?;
-            tetro_coord.1 = row as i8 - ttrys.cur_position.1;
-            for col in 0..STACK_NUM_COLS {
-                let mut block = ttrys.stack[row * STACK_NUM_COLS + col];
-
-                // rasterize the current piece
-                if let Some(tetro) = ttrys.cur_tetro {
-                    tetro_coord.0 = col as i8 - ttrys.cur_position.0;
-                    if (0..=3).contains(&tetro_coord.0) && (-3..=0).contains(&tetro_coord.1) {
-                        if TETROMINO_DATA[tetro as usize]
-                            [<RotationState as Into<usize>>::into(ttrys.cur_state)]
-                        .contains(&tetro_coord)
-                        {
-                            block = Mino::Occupied(tetro_color(tetro))
-                        }
-                    }
-                }
+    // Print a line centered over the stack, the longer ones overflow on both sides
+    fn print_centered(&self, canvas: &mut Canvas, ttrys: &Ttrys, row: u16, text: &str) {
+        let width = Self::board_size(ttrys, self.style).0;
+        let left = self.origin + Self::PADDING_LEFT + 1;
+        let length = text.chars().count() as u16;
+        if length <= width {
+            canvas.move_to(left, row);
+            canvas.print(format!("{:^1$}", text, width as usize));
+        } else {
+            canvas.move_to(left.saturating_sub((length - width).div_ceil(2)), row);
+            canvas.print(text);
+        }
+    }
 
-                match block {
-                    Mino::Occupied(color) => {
-                        s.queue(style::SetBackgroundColor(color))?;
-                        s.queue(style::Print("  "))?;
-                        s.queue(style::ResetColor)?;
-                    }
-                    Mino::PendingClear => {
-                        s.queue(style::SetBackgroundColor(Color::White))?;
-                        s.queue(style::Print("<>"))?;
-                        s.queue(style::ResetColor)?;
-                    }
-                    _ => {
-                        s.queue(style::Print("  "))?;
-                    }
-                }
-            }
-            s.queue(style::Print("║\n"))?;
+    // Lines to draw over the board, none to stop
+    fn set_overlay(&mut self, lines: Vec<(String, Attribute)>) {
+        self.overlay = lines;
+    }
+
+    // The overlay lines centered over the stack, their blanks leaving it visible
+    fn render_overlay(&self, canvas: &mut Canvas, ttrys: &Ttrys) {
+        let (width, board_rows) = Self::board_size(ttrys, self.style);
+        let lines = &self.overlay[..self.overlay.len().min(board_rows as usize)];
+        let center = self.origin + Self::PADDING_LEFT + 1 + width / 2;
+        let first_row = Self::TOP_ROW + (board_rows - lines.len() as u16) / 2 + 1;
+        for ((line, attribute), row) in lines.iter().zip(first_row..) {
+            let length = line.chars().count() as u16;
+            canvas.move_to(center.saturating_sub(length / 2), row);
+            canvas.set_attribute(*attribute);
+            canvas.print_over(line);
+            canvas.set_attribute(Attribute::Reset);
         }
+    }
 
-        // stack bottom
-        s.queue(cursor::MoveToColumn(padding_left))?;
-        s.queue(style::Print("╚"))?;
-        let horiz_border = "═".repeat(2);
-        for _ in 0..STACK_NUM_COLS {
-            s.queue(style::Print(&horiz_border))?;
-        }
-        s.queue(style::Print("╝"))?;
-
-        // draw next tetromino
-        s.queue(cursor::SavePosition)?;
-        let tetro = ttrys.sequence.peek();
-        s.queue(cursor::MoveToPreviousLine(STACK_NUM_ROWS as u16))?;
-        s.queue(cursor::MoveToColumn(
-            padding_left as u16 + 2 + 2 * STACK_NUM_COLS as u16 + 5,
-        ))?;
-        let position = cursor::position().unwrap();
-        s.queue(style::ResetColor)?;
-        for _ in 0..4 {
-            s.queue(style::Print("        "))?;
-            s.queue(cursor::MoveLeft(8))?;
-            s.queue(cursor::MoveDown(1))?;
-        }
-        s.queue(cursor::MoveTo(position.0, position.1))?;
-
-        let minos: [(i8, i8); 4] = TETROMINO_DATA[tetro as usize][0];
-        s.queue(style::SetBackgroundColor(tetro_color(tetro)))?;
-        for mino in minos {
-            if mino.0 > 0 {
-                s.queue(cursor::MoveRight(2 * mino.0 as u16))?;
-            }
-            if mino.1 < 0 {
-                s.queue(cursor::MoveDown(-(mino.1) as u16))?;
-            }
-            s.queue(style::Print("  "))?;
-            s.queue(cursor::MoveTo(position.0, position.1))?;
-        }
-        s.queue(style::ResetColor)?;
-        s.queue(cursor::RestorePosition)?;
-
-        // show score / level
-        s.queue(cursor::SavePosition)?;
-        s.queue(cursor::MoveToPreviousLine(3))?;
-        s.queue(cursor::MoveToColumn(
-            padding_left as u16 + 2 + 2 * STACK_NUM_COLS as u16 + 5,
-        ))?;
-        s.queue(style::Print(format!("Level: {:}", ttrys.level)))?;
-        s.queue(cursor::MoveToColumn(
-            padding_left as u16 + 2 + 2 * STACK_NUM_COLS as u16 + 5,
-        ))?;
-        s.queue(cursor::MoveDown(1))?;
-        s.queue(style::Print(format!("Score: {}", ttrys.score.1)))?;
-        s.queue(cursor::MoveToColumn(
-            padding_left as u16 + 2 + 2 * STACK_NUM_COLS as u16 + 5,
-        ))?;
-        s.queue(cursor::MoveDown(1))?;
-        s.queue(style::Print(format!("Lines: {}", ttrys.score.0)))?;
-        s.queue(cursor::RestorePosition)?;
-
-        s.queue(cursor::MoveToPreviousLine((STACK_NUM_ROWS + 1) as u16))?;
-
-        s.flush().ok();
-
-        s.execute(EndSynchronizedUpdate)?;
-
-        Ok(s)
-    }
-}
-
-impl Drop for GameScreen {
-    fn drop(&mut self) {
-        let mut stdout = stdout();
-        stdout.queue(cursor::Show).ok(); // TODO: panic in drop ?
+    // Column of the stack drawn at a screen position, None outside of the stack
+    fn board_column(&self, ttrys: &Ttrys, column: u16, row: u16) -> Option<usize> {
+        let (board_cols, board_rows) = Self::board_size(ttrys, self.style);
+        let left = self.origin + Self::PADDING_LEFT + 1;
+        if !(left..left + board_cols).contains(&column)
+            || !(Self::TOP_ROW + 1..=Self::TOP_ROW + board_rows).contains(&row)
+        {
+            return None;
+        }
+        let col = ((column - left) * ttrys.width() as u16 / board_cols) as usize;
+        Some(if self.mirrored {
+            ttrys.width() - 1 - col
+        } else {
+            col
+        })
     }
-}
 
-fn duration_from_level(level: u32) -> Duration {
-    // the model is:
-    //    * level base_level..=top_level: a power function with fixed power b
-    //    * level < base_level or level > topLevel: constant function
-    const BASE_LEVEL: f32 = 0.0;
-    const TOP_LEVEL: f32 = 10.0;
-    const B: f32 = 0.7;
-    const MIN_DURATION: f32 = 150.0;
-    const MAX_DURATION: f32 = 600.0;
+    fn set_origin(&mut self, column: u16) {
+        self.origin = column;
+    }
 
-    let level = level as f32;
-    if level < BASE_LEVEL {
-        Duration::from_millis(MAX_DURATION as _)
-    } else if level > TOP_LEVEL {
-        Duration::from_millis(MIN_DURATION as _)
-    } else {
-        let a = (MAX_DURATION - MIN_DURATION) / (BASE_LEVEL.powf(B) - TOP_LEVEL.powf(B));
-        let c = ((MAX_DURATION * TOP_LEVEL.powf(B)) - (MIN_DURATION * BASE_LEVEL.powf(B)))
-            / (TOP_LEVEL.powf(B) - BASE_LEVEL.powf(B));
-        let millis = a * level.powf(B) + c;
-        Duration::from_millis(millis as _)
+    // Forget the last frame, for a screen drawn from scratch
+    fn clear(&mut self) {
+        self.screen.canvas().clear();
     }
-}
 
-fn main() {
-    let mut ttrys = Ttrys::new();
-    let display = GameScreen::new();
-
-    let mut timeout = Timeout::new(Duration::default());
-    while ttrys.running() {
-        display.draw(&ttrys).ok();
-        while !timeout.expired() {
-            if let Some(action) = get_user_action(&timeout) {
-                ttrys.update(action);
-                break;
+    // Apply the player's settings, they take effect with the next frame
+    fn set_settings(&mut self, settings: &Settings, theme: Theme) {
+        self.preview_count = settings.preview_count;
+        self.danger_margin = settings.danger_margin;
+        self.style = settings.style;
+        self.ghost = settings.ghost;
+        self.sound = settings.sound;
+        self.theme = theme;
+    }
+
+    fn set_pause_settings(&mut self, pause_settings: bool) {
+        self.pause_settings = pause_settings;
+    }
+
+    fn toggle_droughts(&mut self) {
+        self.show_droughts = !self.show_droughts;
+    }
+
+    // The keys to list in the help
+    fn set_help(&mut self, keymap: &KeyMap) {
+        self.help = menu::bindings(keymap)
+            .into_iter()
+            .map(|(keys, action)| format!("{:<15}{}", menu::action_name(action), keys))
+            .collect();
+    }
+
+    fn toggle_help(&mut self) {
+        self.show_help = !self.show_help;
+    }
+
+    // Whether the screen changes over time even if the game does not
+    fn animated(&self) -> bool {
+        ((self.in_danger || self.clear_started.is_some() || !self.effects.is_empty())
+            && self.motion())
+            || !self.locked_cells.is_empty()
+            || !self.toasts.is_empty()
+    }
+
+    // Whether the flashing effects and animations are shown
+    fn motion(&self) -> bool {
+        !self.reduced_motion && !self.degraded
+    }
+
+    // Start the effects giving feedback on what just happened in the game
+    fn add_events(&mut self, events: &[GameEvent]) {
+        let now = Instant::now();
+        // the fading of the invisible mode is part of the game, it is kept in reduced motion
+        for event in events {
+            if let GameEvent::PieceLocked { minos, .. } = event {
+                self.locked_cells.extend(minos.map(|cell| (cell, now)));
             }
         }
-        if timeout.expired() {
-            let step_duration = duration_from_level(ttrys.level());
-            timeout = Timeout::new(step_duration);
-            ttrys.step();
+        // so are the toasts, they tell what happened
+        self.toasts
+            .extend(toasts(events).into_iter().map(|toast| (now, toast)));
+        if !self.motion() {
+            return;
+        }
+        let animated = events.iter().filter(|event| {
+            matches!(
+                event,
+                GameEvent::HardDrop { .. } | GameEvent::PieceLocked { .. }
+            )
+        });
+        self.effects.extend(animated.map(|&event| (now, event)));
+    }
+
+    fn set_diagnostics(&mut self, diagnostics: String) {
+        self.diagnostics = Some(diagnostics);
+    }
+
+    // Practice aid: draw the board mirrored left to right
+    fn set_mirrored(&mut self, mirrored: bool) {
+        self.mirrored = mirrored;
+    }
+
+    // spawn state of a piece as shown in the previews, mirrored along with the board
+    fn preview(&self, ttrys: &Ttrys, tetro: Tetromino) -> [(i8, i8); 4] {
+        let minos = ttrys.preview(tetro);
+        if self.mirrored {
+            let left = minos.iter().map(|mino| mino.0).min().unwrap_or(0);
+            let right = minos.iter().map(|mino| mino.0).max().unwrap_or(0);
+            minos.map(|(x, y)| (left + right - x, y))
+        } else {
+            minos
+        }
+    }
+
+    fn draw(&mut self, ttrys: &Ttrys) -> crossterm::Result<()> {
+        let mut screen = self.begin_frame()?;
+        self.render(screen.canvas(), ttrys);
+        self.end_frame(screen)
+    }
+
+    // The screen is drawn into while it is taken out of the game screen, and put back once sent
+    fn begin_frame(&mut self) -> crossterm::Result<Screen> {
+        let mut screen = std::mem::take(&mut self.screen);
+        let (width, height) = crossterm::terminal::size()?;
+        screen.fit(&mut stdout(), width, height)?;
+        Ok(screen)
+    }
+
+    fn end_frame(&mut self, mut screen: Screen) -> crossterm::Result<()> {
+        let started = Instant::now();
+        let result = screen.present(&mut stdout());
+        self.screen = screen;
+
+        // Keep track of the time it takes the terminal to take a frame. Past half the frame
+        // interval, the animations are turned off and the HUD is drawn less often, until it gets
+        // well below again.
+        self.draw_time = (self.draw_time * 7 + started.elapsed()) / 8;
+        if self.draw_time > FRAME_INTERVAL / 2 {
+            self.degraded = true;
+        } else if self.draw_time < FRAME_INTERVAL / 4 {
+            self.degraded = false;
+        }
+
+        result
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, ttrys: &Ttrys) {
+        use crossterm::style;
+
+        self.theme.set_level(ttrys.level());
+
+        let padding_left = self.origin + Self::PADDING_LEFT;
+        let num_cols = ttrys.width();
+        let num_rows = ttrys.height();
+        let (board_cols, board_rows) = Self::board_size(ttrys, self.style);
+        let frame = self.frame_chars();
+        let danger_height = num_rows.saturating_sub(self.danger_margin) as i8;
+
+        // When the stack gets too high, shift the border color, flash the top rows
+        // and ring the terminal bell once when entering the danger zone (the bell goes out with
+        // the next frame).
+        let danger = ttrys.stack_height() >= danger_height;
+        if danger && !self.in_danger && self.sound {
+            stdout().queue(style::Print("\x07")).ok();
+        }
+        self.in_danger = danger;
+        let border_color = if danger {
+            Self::DANGER_COLOR
+        } else {
+            self.theme.border_color()
+        };
+        // the flashing rows would pass for minos in ASCII
+        let flash = danger && self.motion() && self.heartbeat() && self.style != RenderStyle::Ascii;
+
+        // rows being cleared flash on and off
+        if ttrys.state() == State::ClearRows {
+            self.clear_started.get_or_insert_with(Instant::now);
+        } else {
+            self.clear_started = None;
+        }
+        let clear_lit = self.clear_started.is_none_or(|start| {
+            (start.elapsed().as_millis() / Self::CLEAR_FLASH_PERIOD.as_millis()) % 2 == 0
+        });
+
+        // A hard dropped piece leaves a trail in the cells it went through, which fades out. A
+        // locked piece flashes white.
+        self.effects.retain(|(start, event)| match event {
+            GameEvent::HardDrop { .. } => start.elapsed() < Self::TRAIL_DURATION,
+            GameEvent::PieceLocked { .. } => start.elapsed() < Self::LOCK_FLASH_DURATION,
+            _ => false,
+        });
+        let mut trail = Vec::new();
+        let mut lock_flash = Vec::new();
+        for (start, event) in &self.effects {
+            match *event {
+                GameEvent::HardDrop { tetro, from, to } => {
+                    let faded = start.elapsed() >= Self::TRAIL_DURATION / 2;
+                    for &(col, row) in &to {
+                        let top = from
+                            .iter()
+                            .filter(|mino| mino.0 == col)
+                            .map(|mino| mino.1)
+                            .max()
+                            .unwrap_or(row);
+                        trail.extend((row + 1..=top).map(|row| ((col, row), tetro, faded)));
+                    }
+                }
+                GameEvent::PieceLocked { minos, .. } => lock_flash.extend(minos),
+                _ => (),
+            }
+        }
+
+        // In invisible games the stack is hidden but for the cells locked lately, it is revealed
+        // when the game ends
+        self.locked_cells
+            .retain(|(_, locked)| locked.elapsed() < Self::INVISIBLE_DELAY);
+        self.toasts
+            .retain(|(start, _)| start.elapsed() < Self::TOAST_DURATION);
+        let invisible = ttrys.options().mode == Mode::Invisible && ttrys.state() != State::End;
+
+        // stack top
+        canvas.move_to(padding_left, Self::TOP_ROW);
+        canvas.set_foreground(border_color);
+        canvas.print(self.frame_top("", board_cols));
+        canvas.reset_color();
+
+        // Draw the stack content, including the current piece. This way there is
+        // no intermediate state with the piece blanked out. Alternative would be
+        // to rasterize the piece in a copy of a the stack.
+        // While paused, the stack and the pieces are hidden so the pause can't be used to plan
+        // ahead.
+        let paused = ttrys.state() == State::Paused;
+        let piece = ttrys.piece().filter(|_| !paused);
+        let ghost = ttrys.ghost().filter(|_| !paused && self.ghost);
+        let look = |col: usize, row: usize| {
+            let mut block = if paused {
+                Mino::Free
+            } else {
+                ttrys.mino(col, row)
+            };
+            let cell = (col as i8, row as i8);
+
+            let mut fading = false;
+            if invisible && matches!(block, Mino::Occupied(_) | Mino::Garbage) {
+                match self
+                    .locked_cells
+                    .iter()
+                    .rfind(|(locked, _)| *locked == cell)
+                {
+                    Some((_, locked)) => {
+                        fading = locked.elapsed() >= Self::INVISIBLE_DELAY / 2;
+                    }
+                    None => block = Mino::Free,
+                }
+            }
+
+            // rasterize the current piece
+            if let Some((tetro, minos)) = piece {
+                if minos.contains(&cell) {
+                    block = Mino::Occupied(tetro)
+                }
+            }
+
+            // rasterize the ghost piece at the landing position, where the stack is free
+            let ghost_tetro = piece
+                .map(|(tetro, _)| tetro)
+                .filter(|_| ghost.is_some_and(|minos| minos.contains(&cell)));
+
+            let trail_at = trail.iter().find(|effect| effect.0 == cell);
+
+            match block {
+                Mino::Free if ghost_tetro.is_some() => {
+                    CellLook::Ghost(self.theme.ghost_color(ghost_tetro.unwrap()))
+                }
+                Mino::Occupied(_) if lock_flash.contains(&cell) => CellLook::Solid(Color::White),
+                Mino::Occupied(tetro) if fading => CellLook::Fading(self.theme.tetro_color(tetro)),
+                Mino::Occupied(tetro) => CellLook::Solid(self.theme.tetro_color(tetro)),
+                Mino::Garbage => CellLook::Solid(self.theme.garbage_color()),
+                Mino::Free if trail_at.is_some() => {
+                    let &(_, tetro, faded) = trail_at.unwrap();
+                    CellLook::Trail(self.theme.tetro_color(tetro), faded)
+                }
+                Mino::PendingClear if !self.motion() => CellLook::Clearing(None),
+                Mino::PendingClear if clear_lit => {
+                    CellLook::Clearing(Some(self.theme.clear_color()))
+                }
+                Mino::PendingClear => CellLook::Empty,
+                _ if flash && row as i8 >= danger_height => CellLook::Solid(Color::DarkRed),
+                _ => CellLook::Empty,
+            }
+        };
+        // the left border shows the garbage rows about to be received
+        let garbage = ttrys.pending_garbage();
+        for screen_row in 0..board_rows {
+            // the lowest of the rows drawn on this line, the half blocks draw two of them
+            let row = match self.style {
+                RenderStyle::HalfBlock => 2 * (board_rows - 1 - screen_row) as usize,
+                RenderStyle::DoubleSpace | RenderStyle::Ascii => num_rows - 1 - screen_row as usize,
+            };
+            canvas.move_to(padding_left, Self::TOP_ROW + 1 + screen_row);
+            canvas.set_foreground(if row < garbage {
+                Self::DANGER_COLOR
+            } else {
+                border_color
+            });
+            canvas.print(frame[3]);
+            canvas.reset_color();
+            for screen_col in 0..num_cols {
+                let col = if self.mirrored {
+                    num_cols - 1 - screen_col
+                } else {
+                    screen_col
+                };
+                if self.style == RenderStyle::HalfBlock {
+                    let top = (row + 1 < num_rows).then(|| look(col, row + 1));
+                    self.print_half_block(
+                        canvas,
+                        top.and_then(|look| self.cell_fill(look)),
+                        self.cell_fill(look(col, row)),
+                    );
+                } else {
+                    self.print_cell(canvas, look(col, row), self.theme.background_color());
+                }
+            }
+            canvas.set_foreground(border_color);
+            canvas.print(frame[3]);
+            canvas.reset_color();
+        }
+
+        // stack bottom
+        canvas.move_to(padding_left, Self::TOP_ROW + board_rows + 1);
+        canvas.set_foreground(border_color);
+        canvas.print(self.frame_bottom(board_cols));
+        canvas.reset_color();
+
+        if let State::Countdown(count) = ttrys.state() {
+            let row = Self::TOP_ROW + board_rows - board_rows / 2;
+            let text = match count {
+                0 => String::from("GO!"),
+                count => count.to_string(),
+            };
+            canvas.set_attribute(style::Attribute::Bold);
+            self.print_centered(canvas, ttrys, row, &text);
+            canvas.set_attribute(style::Attribute::Reset);
+        }
+        if paused {
+            let row = Self::TOP_ROW + board_rows - board_rows / 2;
+            canvas.set_attribute(style::Attribute::Bold);
+            self.print_centered(canvas, ttrys, row, "PAUSED");
+            canvas.set_attribute(style::Attribute::Reset);
+            if self.pause_settings {
+                canvas.set_attribute(style::Attribute::Dim);
+                self.print_centered(canvas, ttrys, row + 1, "[Tab] settings");
+                canvas.set_attribute(style::Attribute::Reset);
+            }
+        }
+        self.render_overlay(canvas, ttrys);
+
+        // the HUD around the board waits for a later frame when drawing is late, unless the
+        // game is paused or over and the board alone would be out of date
+        self.frame += 1;
+        if !self.degraded
+            || self.frame.is_multiple_of(Self::DEGRADED_HUD_INTERVAL)
+            || matches!(ttrys.state(), State::Paused | State::End)
+        {
+            self.draw_hud(canvas, ttrys);
+        }
+    }
+
+    // Previews, held piece, statistics and counters around the board
+    fn draw_hud(&self, canvas: &mut Canvas, ttrys: &Ttrys) {
+        use crossterm::style;
+
+        let hold_column = self.origin + 2;
+        let (board_cols, board_rows) = Self::board_size(ttrys, self.style);
+        let frame = self.frame_chars();
+        let right_column = self.origin + Self::PADDING_LEFT + 2 + board_cols + 5;
+        let bottom_row = Self::TOP_ROW + board_rows + 1;
+        let paused = ttrys.state() == State::Paused;
+
+        // draw the next tetrominoes in a column, each on two rows followed by a blank one
+        let preview_count = self.preview_count.min(board_rows as usize / 3);
+        let upcoming = if paused {
+            Vec::new()
+        } else {
+            ttrys.upcoming(preview_count)
+        };
+        for slot in 0..preview_count {
+            let piece = upcoming
+                .get(slot)
+                .map(|&tetro| (tetro, self.preview(ttrys, tetro)));
+            for row in 0..3 {
+                canvas.move_to(
+                    right_column,
+                    Self::TOP_ROW + 1 + 3 * slot as u16 + row as u16,
+                );
+                for col in 0..4 {
+                    match piece {
+                        Some((tetro, minos)) if minos.contains(&(col, -row)) => {
+                            self.print_cell(
+                                canvas,
+                                CellLook::Solid(self.theme.tetro_color(tetro)),
+                                Color::Reset,
+                            );
+                        }
+                        _ => {
+                            canvas.print("  ");
+                        }
+                    }
+                }
+            }
+        }
+
+        // draw held tetromino, dimmed when it can't be swapped before the next spawn
+        canvas.move_to(hold_column, Self::TOP_ROW);
+        canvas.set_foreground(self.theme.border_color());
+        canvas.print(self.frame_top("Hold", 8));
+        canvas.reset_color();
+        for row in 0..2 {
+            canvas.move_to(hold_column, Self::TOP_ROW + 1 + row as u16);
+            canvas.set_foreground(self.theme.border_color());
+            canvas.print(frame[3]);
+            canvas.reset_color();
+            for col in 0..4 {
+                match ttrys.hold().filter(|_| !paused) {
+                    Some(tetro) if self.preview(ttrys, tetro).contains(&(col, -row)) => {
+                        let color = if ttrys.hold_used() {
+                            Color::DarkGrey
+                        } else {
+                            self.theme.tetro_color(tetro)
+                        };
+                        self.print_cell(canvas, CellLook::Solid(color), Color::Reset);
+                    }
+                    _ => {
+                        canvas.print("  ");
+                    }
+                }
+            }
+            canvas.set_foreground(self.theme.border_color());
+            canvas.print(frame[3]);
+            canvas.reset_color();
+        }
+        canvas.move_to(hold_column, Self::TOP_ROW + 3);
+        canvas.set_foreground(self.theme.border_color());
+        canvas.print(self.frame_bottom(8));
+        canvas.reset_color();
+
+        // statistics sidebar below the held tetromino, cut short on low stacks
+        let stats = ttrys.stats();
+        let clears = stats.clears();
+        let mut sidebar = vec![
+            (None, format!("{:<6}{:>5}", "Lines", stats.lines())),
+            (None, format!("{:<6}{:>5}", "Single", clears[0])),
+            (None, format!("{:<6}{:>5}", "Double", clears[1])),
+            (None, format!("{:<6}{:>5}", "Triple", clears[2])),
+            (None, format!("{:<6}{:>5}", "Tetris", clears[3])),
+            (
+                None,
+                format!("{:<6}{:>5.2}", "PPS", stats.pieces_per_second()),
+            ),
+            (None, String::new()),
+        ];
+        sidebar.extend((0..7).map(|idx| {
+            let tetro: Tetromino = idx.into();
+            (Some(tetro), format!("{:>10}", stats.tetrominoes(tetro)))
+        }));
+        for (index, (tetro, line)) in sidebar
+            .iter()
+            .take(board_rows.saturating_sub(3) as usize)
+            .enumerate()
+        {
+            canvas.move_to(hold_column, Self::TOP_ROW + 5 + index as u16);
+            if let Some(tetro) = tetro {
+                canvas.set_foreground(self.theme.tetro_color(*tetro));
+                canvas.print(tetro);
+                canvas.reset_color();
+            }
+            canvas.print(line);
+        }
+
+        // show the drought counter, below the preview column
+        if self.show_droughts {
+            let drought_row = 3 * preview_count.max(2) as u16 + 1;
+            canvas.move_to(right_column, (Self::TOP_ROW + drought_row).min(bottom_row));
+            let drought = ttrys.droughts().current(Tetromino::I);
+            if drought >= Self::DROUGHT_WARNING {
+                canvas.set_foreground(Color::Red);
+            }
+            canvas.print(format!("Since last I: {:<3}", drought));
+            canvas.reset_color();
+        }
+
+        // show score / level, the lines above them are left out of boards shorter than the HUD
+        if let (GravityPolicy::Adaptive(adaptive), Some(row)) =
+            (ttrys.gravity(), bottom_row.checked_sub(4))
+        {
+            canvas.move_to(right_column, row);
+            canvas.print(format!("Speed: {:.1} ", adaptive.speed()));
+        }
+        canvas.move_to(right_column, bottom_row - 3);
+        canvas.print(format!("Level: {:}", ttrys.level()));
+        canvas.move_to(right_column, bottom_row - 2);
+        canvas.print(format!("Score: {}", ttrys.score().1));
+        // the scoring, unless the usual one
+        if ttrys.options().scoring != Scoring::Ttrys {
+            canvas.print(format!(" ({})", ttrys.options().scoring));
+        }
+        // combo and back-to-back chain, next to the score while they last
+        let mut chains = Vec::new();
+        if ttrys.combo() > 1 {
+            chains.push(format!("Combo {}", ttrys.combo()));
+        }
+        if ttrys.back_to_back() > 0 {
+            chains.push(format!("B2B x{}", ttrys.back_to_back()));
+        }
+        canvas.set_foreground(Color::Yellow);
+        canvas.print(format!("  {:<20}", chains.join("  ")));
+        canvas.reset_color();
+        canvas.move_to(right_column, bottom_row - 1);
+        canvas.print(format!("Lines: {}", ttrys.score().0));
+
+        // lines to go, and the sprint clock
+        if let Some(remaining) = ttrys.lines_remaining() {
+            canvas.print(format!("  ({} to go) ", remaining));
+        }
+        if let (Mode::Sprint, Some(row)) = (ttrys.options().mode, bottom_row.checked_sub(5)) {
+            canvas.move_to(right_column, row);
+            canvas.print(format!("Time: {}", format_time(ttrys.elapsed())));
+        }
+
+        // the toasts, under the score
+        let toasts = self
+            .toasts
+            .iter()
+            .map(|(_, toast)| toast.as_str())
+            .collect::<Vec<_>>()
+            .join("  ");
+        let fading = self
+            .toasts
+            .iter()
+            .all(|(start, _)| start.elapsed() >= Self::TOAST_DURATION / 2);
+        canvas.move_to(right_column, bottom_row + 1);
+        canvas.set_attribute(if fading {
+            style::Attribute::Dim
+        } else {
+            style::Attribute::Bold
+        });
+        canvas.set_foreground(Color::Yellow);
+        canvas.print(format!("{:<1$}", toasts, Self::TOAST_WIDTH as usize));
+        canvas.set_attribute(style::Attribute::Reset);
+
+        if self.show_help {
+            self.draw_help(canvas, ttrys);
+        }
+
+        if let Some(diagnostics) = &self.diagnostics {
+            canvas.move_to(right_column, bottom_row);
+            canvas.set_attribute(style::Attribute::Dim);
+            canvas.print(format!("{:<30}", diagnostics));
+            canvas.set_attribute(style::Attribute::Reset);
+        }
+    }
+
+    // The keys of the layout, right of the HUD when the terminal is wide enough, else under the
+    // board in as many columns as fit. Dimmed during play, so as not to draw the eye.
+    fn draw_help(&self, canvas: &mut Canvas, ttrys: &Ttrys) {
+        use crossterm::style;
+
+        let board_rows = Self::board_size(ttrys, self.style).1;
+        let beside = self.origin + Self::columns(ttrys, self.style);
+        let (column, top, columns, rows) = if beside + Self::HELP_WIDTH <= canvas.width() {
+            (beside, Self::TOP_ROW, 1, board_rows + 2)
+        } else {
+            let top = Self::TOP_ROW + board_rows + 3;
+            let columns = canvas.width().saturating_sub(self.origin + 2) / Self::HELP_WIDTH;
+            let rows = canvas.height().saturating_sub(top);
+            (self.origin + 2, top, columns.max(1), rows)
+        };
+        // the title takes a row, and the last place tells of the keys left out
+        let capacity = columns as usize * rows.saturating_sub(1) as usize;
+        if capacity == 0 {
+            return;
+        }
+        let shown = if self.help.len() <= capacity {
+            self.help.len()
+        } else {
+            capacity - 1
+        };
+        let mut lines: Vec<&str> = self.help[..shown].iter().map(String::as_str).collect();
+        if shown < self.help.len() {
+            lines.push("...");
+        }
+
+        let dim = ttrys.state() != State::Paused;
+        canvas.move_to(column, top);
+        canvas.set_attribute(style::Attribute::Bold);
+        canvas.print("Keys");
+        canvas.set_attribute(style::Attribute::Reset);
+        for (index, line) in lines.iter().enumerate() {
+            let (slot_column, slot_row) = (index as u16 / (rows - 1), index as u16 % (rows - 1));
+            canvas.move_to(column + slot_column * Self::HELP_WIDTH, top + 1 + slot_row);
+            if dim {
+                canvas.set_attribute(style::Attribute::Dim);
+            }
+            canvas.print(line);
+            canvas.set_attribute(style::Attribute::Reset);
+        }
+    }
+
+    // Draw the board with lines of text centered over the stack, the first one in bold. The lines
+    // that don't fit below each other are left out.
+    fn draw_panel(&mut self, ttrys: &Ttrys, lines: &[String]) -> crossterm::Result<()> {
+        let mut screen = self.begin_frame()?;
+        self.render(screen.canvas(), ttrys);
+        self.render_panel(screen.canvas(), ttrys, lines);
+        self.end_frame(screen)
+    }
+
+    // Draw the game with the board of the opponent of a network game on the right, once received
+    fn draw_with_opponent(
+        &mut self,
+        ttrys: &Ttrys,
+        opponent: Option<&Board>,
+    ) -> crossterm::Result<()> {
+        let mut screen = self.begin_frame()?;
+        self.render(screen.canvas(), ttrys);
+        if let Some(board) = opponent {
+            let column = self.origin + Self::columns(ttrys, self.style);
+            self.render_opponent(screen.canvas(), column, board);
+        }
+        self.end_frame(screen)
+    }
+
+    // The opponent's board is drawn two columns a cell, whatever the style
+    fn render_opponent(&self, canvas: &mut Canvas, column: u16, board: &Board) {
+        let border_color = self.theme.border_color();
+        let frame = self.frame_chars();
+        canvas.move_to(column, Self::TOP_ROW);
+        canvas.set_foreground(border_color);
+        canvas.print(self.frame_top("Opponent", 2 * board.width as u16));
+        canvas.reset_color();
+        for row in (0..board.height).rev() {
+            canvas.move_to(column, Self::TOP_ROW + (board.height - row) as u16);
+            canvas.set_foreground(border_color);
+            canvas.print(frame[3]);
+            canvas.reset_color();
+            for col in 0..board.width {
+                let color = match board.cells[row * board.width + col] {
+                    Mino::Free => None,
+                    Mino::Occupied(tetro) => Some(self.theme.tetro_color(tetro)),
+                    Mino::Garbage => Some(self.theme.garbage_color()),
+                    Mino::PendingClear => Some(self.theme.clear_color()),
+                };
+                let look = color.map_or(CellLook::Empty, CellLook::Solid);
+                self.print_cell(canvas, look, self.theme.background_color());
+            }
+            canvas.set_foreground(border_color);
+            canvas.print(frame[3]);
+            canvas.reset_color();
+        }
+        canvas.move_to(column, Self::TOP_ROW + board.height as u16 + 1);
+        canvas.set_foreground(border_color);
+        canvas.print(self.frame_bottom(2 * board.width as u16));
+        canvas.reset_color();
+    }
+
+    fn render_panel(&self, canvas: &mut Canvas, ttrys: &Ttrys, lines: &[String]) {
+        use crossterm::style;
+
+        let board_rows = Self::board_size(ttrys, self.style).1 as usize;
+        let lines = &lines[..lines.len().min(board_rows)];
+
+        let first_row = Self::TOP_ROW + ((board_rows - lines.len()) / 2 + 1) as u16;
+        for (index, line) in lines.iter().enumerate() {
+            if index == 0 {
+                canvas.set_attribute(style::Attribute::Bold);
+            }
+            self.print_centered(canvas, ttrys, first_row + index as u16, line);
+            canvas.set_attribute(style::Attribute::Reset);
+        }
+    }
+}
+
+static TERMINAL_TAKEN: AtomicBool = AtomicBool::new(false);
+
+// Bring the terminal back to its original state: normal screen, cooked mode, visible cursor, no
+// mouse reports. Only once: leaving the alternate screen again would move the cursor back over what
+// was printed since.
+fn restore_terminal() {
+    use crossterm::event::DisableMouseCapture;
+    use crossterm::terminal::{disable_raw_mode, LeaveAlternateScreen};
+
+    if !TERMINAL_TAKEN.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    report_key_releases(false);
+    disable_raw_mode().ok();
+    let mut stdout = stdout();
+    stdout.queue(DisableMouseCapture).ok();
+    stdout.queue(LeaveAlternateScreen).ok();
+    stdout.execute(cursor::Show).ok();
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("audit-rng") {
+        args.next();
+        if let Err(message) = audit::run(args) {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("config") {
+        args.next();
+        if let Err(message) = bundle::run(args) {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+        return;
+    }
+
+    let mut adaptive = false;
+    let mut floor_slide = false;
+    let mut layout = Layout::Default;
+    let mut reduced_motion = false;
+    let mut demo = false;
+    let mut ascii = !utf8_locale();
+    let mut measure_latency = false;
+    // None picks it depending on the power source
+    let mut powersave = None;
+    // None follows the power saving mode
+    let mut fps = None;
+    let mut profile = RenderProfile::Standard;
+    let mut preview_count = None;
+    let mut mirror = Mirror::None;
+    // Some(None) detects it whatever the settings say
+    let mut palette = None;
+    let mut show_scores = false;
+    let mut resume = false;
+    let mut options = Options::default();
+    let mut rules = String::from("guideline");
+    let mut export_path = None;
+    let mut versus = false;
+    let mut host = false;
+    let mut port = netplay::DEFAULT_PORT;
+    let mut connect = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--layout" => {
+                layout = match args.next().as_deref().and_then(Layout::from_name) {
+                    Some(layout) => layout,
+                    None => {
+                        eprintln!("--layout expects one of: default, one-handed, one-handed-left");
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--rules" => {
+                rules = args.next().unwrap_or_default();
+                if rotation::from_preset(&rules).is_none() {
+                    eprintln!("--rules expects one of: guideline, tgm, classic");
+                    std::process::exit(2);
+                }
+            }
+            "--export" => {
+                export_path = match args.next() {
+                    Some(path) => Some(std::path::PathBuf::from(path)),
+                    None => {
+                        eprintln!("--export expects a file name");
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--level" => {
+                options.level = match args.next().and_then(|level| level.parse().ok()) {
+                    Some(level) => level,
+                    None => {
+                        eprintln!("--level expects a number");
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--randomizer" => {
+                options.randomizer =
+                    match args.next().as_deref().and_then(RandomizerKind::from_name) {
+                        Some(randomizer) => randomizer,
+                        None => {
+                            eprintln!(
+                                "--randomizer expects one of: seven-bag, bag-<1..7>, classic, tgm"
+                            );
+                            std::process::exit(2);
+                        }
+                    }
+            }
+            // the option of the partial bags, before there were other randomizers
+            "--bag-size" => {
+                options.randomizer = match args.next().and_then(|size| size.parse().ok()) {
+                    Some(size @ 1..=7) => RandomizerKind::Bag(size),
+                    _ => {
+                        eprintln!("--bag-size expects a number between 1 and 7");
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--seed" => {
+                options.seed = match args.next().and_then(|seed| seed.parse().ok()) {
+                    Some(seed) => Some(seed),
+                    None => {
+                        eprintln!("--seed expects a number");
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--width" | "--height" => {
+                let size = match args.next().and_then(|size| size.parse().ok()) {
+                    Some(size) if (Options::MIN_SIZE..=Options::MAX_SIZE).contains(&size) => size,
+                    _ => {
+                        eprintln!(
+                            "{} expects a number between {} and {}",
+                            arg,
+                            Options::MIN_SIZE,
+                            Options::MAX_SIZE
+                        );
+                        std::process::exit(2);
+                    }
+                };
+                if arg == "--width" {
+                    options.width = size;
+                } else {
+                    options.height = size;
+                }
+            }
+            "--versus" => versus = true,
+            "--mode" => {
+                options.mode = match args.next().as_deref().and_then(Mode::from_name) {
+                    Some(mode) => mode,
+                    None => {
+                        eprintln!(
+                            "--mode expects one of: classic, marathon, sprint, invisible, cheese"
+                        );
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--host" => host = true,
+            "--port" => {
+                port = match args.next().and_then(|port| port.parse().ok()) {
+                    Some(port) => port,
+                    None => {
+                        eprintln!("--port expects a port number");
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--connect" => {
+                connect = match args.next() {
+                    Some(address) => Some(address),
+                    None => {
+                        eprintln!("--connect expects an address");
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--adaptive" => adaptive = true,
+            "--floor-slide" => floor_slide = true,
+            "--endless" => options.endless = true,
+            "--scores" => show_scores = true,
+            "--resume" => resume = true,
+            "--reduced-motion" => reduced_motion = true,
+            "--ascii" => ascii = true,
+            "--demo" => demo = true,
+            "--latency" => measure_latency = true,
+            "--powersave" => {
+                powersave = match args.next().as_deref() {
+                    Some("on") => Some(true),
+                    Some("off") => Some(false),
+                    Some("auto") => None,
+                    _ => {
+                        eprintln!("--powersave expects one of: auto, on, off");
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--fps" => {
+                fps = match args.next().and_then(|fps| fps.parse().ok()) {
+                    Some(fps) if (1..=MAX_FPS).contains(&fps) => Some(fps),
+                    _ => {
+                        eprintln!("--fps expects a number between 1 and {}", MAX_FPS);
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--preview" => {
+                preview_count = match args.next().and_then(|count| count.parse().ok()) {
+                    Some(count) if count <= TetrominoSequence::MAX_PREVIEW => Some(count),
+                    _ => {
+                        eprintln!(
+                            "--preview expects a number between 0 and {}",
+                            TetrominoSequence::MAX_PREVIEW
+                        );
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--mirror" => {
+                mirror = match args.next().as_deref().and_then(Mirror::from_name) {
+                    Some(mirror) => mirror,
+                    None => {
+                        eprintln!("--mirror expects one of: none, input, board, both");
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--render" => {
+                profile = match args.next().as_deref().and_then(RenderProfile::from_name) {
+                    Some(profile) => profile,
+                    None => {
+                        eprintln!("--render expects one of: standard, competitive");
+                        std::process::exit(2);
+                    }
+                }
+            }
+            "--theme" => {
+                let name = args.next().unwrap_or_default();
+                if Palette::from_name(&name).is_none() && name != "auto" {
+                    eprintln!(
+                        "--theme expects one of: auto, classic, high-contrast-dark, high-contrast-light, \
+                         pastel, monochrome"
+                    );
+                    std::process::exit(2);
+                }
+                palette = Some(Palette::from_name(&name));
+            }
+            _ => {
+                eprintln!("unknown argument '{}'", arg);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    if options.mode != Mode::Classic && (versus || host || connect.is_some()) {
+        eprintln!("versus games are classic ones, --mode cannot be changed");
+        std::process::exit(2);
+    }
+
+    if show_scores {
+        match HighScores::load(options.mode) {
+            Ok(scores) => scores.print(None),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    // the command line has the last word over the settings saved
+    let mut settings = config.settings.clone();
+    if let Some(palette) = palette {
+        settings.palette = palette;
+    }
+    if let Some(count) = preview_count {
+        settings.preview_count = count;
+    }
+    if ascii {
+        settings.style = RenderStyle::Ascii;
+    }
+    let detected_palette = Palette::detect();
+    let theme = Theme::new(
+        settings.palette.unwrap_or(detected_palette),
+        config.piece_colors,
+    );
+    let powersave = powersave.unwrap_or_else(on_battery);
+    let frame_interval = match fps {
+        Some(fps) => Duration::from_secs(1) / fps,
+        None if powersave => POWERSAVE_FRAME_INTERVAL,
+        None => FRAME_INTERVAL,
+    };
+
+    if versus {
+        let columns = GameScreen::columns(&Ttrys::new(options.clone()), settings.style);
+        if crossterm::terminal::size().is_ok_and(|(width, _)| width < 2 * columns) {
+            eprintln!(
+                "the terminal is too narrow for a versus game, {} columns are needed",
+                2 * columns
+            );
+            std::process::exit(2);
+        }
+        let displays = [0, columns].map(|origin| {
+            let mut display = GameScreen::new(theme.clone(), reduced_motion || powersave, profile);
+            display.set_origin(origin);
+            display.set_settings(&settings, theme.clone());
+            display
+        });
+        versus::run(options, &rules, floor_slide, displays, frame_interval);
+        return;
+    }
+
+    if host || connect.is_some() {
+        let link = match &connect {
+            Some(address) => netplay::connect(address),
+            None => netplay::host(port, &options, &rules)
+                .map(|(link, options)| (link, options, rules.clone())),
+        };
+        let (link, options, rules) = match link {
+            Ok(link) => link,
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(2);
+            }
+        };
+        let mut ttrys = Ttrys::new(options);
+        ttrys.set_floor_slide(floor_slide);
+        if let Some(rotation_system) = rotation::from_preset(&rules) {
+            ttrys.set_rotation_system(rotation_system);
+        }
+        let mut display = GameScreen::new(theme.clone(), reduced_motion || powersave, profile);
+        display.set_settings(&settings, theme);
+        netplay::run(ttrys, display, link, KeyMap::new(layout), frame_interval);
+        return;
+    }
+
+    // the notation of each game of the session goes to the export file
+    let mut export = match export_path.map(std::fs::File::create).transpose() {
+        Ok(export) => export,
+        Err(err) => {
+            eprintln!("cannot create the export file: {}", err);
+            std::process::exit(2);
+        }
+    };
+
+    // the saved game is played first, without going through the menu
+    let mut resumed = match resume.then(save::resume).transpose() {
+        Ok(resumed) => resumed,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    // during a game, a termination request saves it before leaving; elsewhere, it ends the program
+    // right away, once the terminal is restored
+    let terminated = Arc::new(AtomicBool::new(false));
+    let idle = Arc::new(AtomicBool::new(true));
+    if let Ok(mut signals) = signal_hook::iterator::Signals::new([signal_hook::consts::SIGTERM]) {
+        let (terminated, idle) = (terminated.clone(), idle.clone());
+        std::thread::spawn(move || {
+            for signal in signals.forever() {
+                terminated.store(true, Ordering::Relaxed);
+                if idle.load(Ordering::Relaxed) {
+                    restore_terminal();
+                    std::process::exit(128 + signal);
+                }
+            }
+        });
+    }
+
+    // the game takes over the screen, the menu comes first and after each game left
+    let mut display = GameScreen::new(theme, reduced_motion || powersave, profile);
+    let mut choices = menu::Choices {
+        options,
+        layout,
+        rules,
+        mirror,
+        adaptive,
+        floor_slide,
+        demo,
+        settings,
+        piece_colors: config.piece_colors,
+        detected_palette,
+        gravity: config.gravity,
+        scoring: config.scoring,
+    };
+    // the summary is about the last game played, messages are kept until the screen is left
+    let mut last_game = None;
+    let mut messages = Vec::new();
+    let mut latency = measure_latency.then(LatencyMeter::new);
+    let audio = Audio::new();
+    // raw mode is on for the whole session, before any input is read: the query below would
+    // otherwise toggle it, and keys pressed early would wait for a line to be complete
+    let terminal = TerminalGuard::new();
+    // asked once and for all, the terminal is read by the input thread from then on
+    let key_releases = std::io::IsTerminal::is_terminal(&std::io::stdin())
+        && crossterm::terminal::supports_keyboard_enhancement().unwrap_or(false);
+    let input = spawn_input();
+    let mut leave = false;
+    while let Some(start) = resumed
+        .as_ref()
+        .map(|_| menu::Start::Game)
+        .or_else(|| menu::run(&mut display, &input, &mut choices))
+    {
+        // a demo started by the idle menu ends with any key, the bot games go in no high score
+        // table nor in the summary
+        let attract = start == menu::Start::Demo;
+        let demo = attract || choices.demo;
+        // a resumed game keeps its own settings
+        let (mut ttrys, rules) = match resumed.take() {
+            Some(saved) => (saved.game, saved.rules),
+            None => {
+                let gravity = choices
+                    .gravity
+                    .iter()
+                    .find(|(mode, _)| *mode == choices.options.mode)
+                    .map(|(_, curve)| curve.clone());
+                let scoring = choices
+                    .scoring
+                    .iter()
+                    .find(|(mode, _)| *mode == choices.options.mode)
+                    .map_or(Scoring::Ttrys, |&(_, scoring)| scoring);
+                let mut ttrys = Ttrys::new(Options {
+                    gravity,
+                    scoring,
+                    ..choices.options.clone()
+                });
+                ttrys.set_gravity(if choices.adaptive {
+                    GravityPolicy::Adaptive(AdaptiveGravity::new())
+                } else {
+                    GravityPolicy::Level
+                });
+                ttrys.set_floor_slide(choices.floor_slide);
+                // the bot needs no warning
+                ttrys.set_countdown(!demo);
+                (ttrys, choices.rules.clone())
+            }
+        };
+        if let Some(rotation_system) = rotation::from_preset(&rules) {
+            ttrys.set_rotation_system(rotation_system);
+        }
+        ttrys.set_spawn_delay(spawn_delay(&choices.settings));
+        let keymap = KeyMap::new(choices.layout);
+        display.set_help(&keymap);
+        let mirror = choices.mirror;
+        display.set_settings(&choices.settings, choices.theme());
+        display.set_pause_settings(true);
+        display.set_mirrored(mirror.board());
+        display.set_overlay(if attract {
+            title_overlay(choices.settings.style)
+        } else {
+            Vec::new()
+        });
+        display.clear();
+
+        let mut scores = match HighScores::load(ttrys.options().mode) {
+            Ok(scores) => Some(scores).filter(|_| !demo),
+            Err(message) => {
+                messages.push(message);
+                None
+            }
+        };
+
+        // rank of the last game in the high score table
+        let mut rank;
+        let mut timeline;
+        loop {
+            timeline = Timeline::new();
+            // the gravity runs on its own timer, the screen is redrawn when something changed, at
+            // most once per frame interval. A countdown starts with its first count on screen.
+            let mut timeout = Timeout::new(match ttrys.state() {
+                State::Countdown(_) => ttrys.step_duration(),
+                _ => Duration::default(),
+            });
+            let mut last_draw: Option<Instant> = None;
+            let mut dirty = true;
+            let mut autoshift = AutoShift::new(&choices.settings, choices.layout, key_releases);
+            report_key_releases(autoshift.enabled());
+            let mut bot = demo.then(Bot::new);
+            let mut bot_due = Instant::now();
+            let mut saving = false;
+            if ttrys.state() == State::Paused {
+                timeout.pause();
+            }
+            idle.store(false, Ordering::Relaxed);
+            while ttrys.running() {
+                // the game is left paused, to be resumed later, bot games are just left
+                if saving || terminated.load(Ordering::Relaxed) {
+                    if !demo {
+                        if ttrys.state() != State::Paused {
+                            ttrys.update(UserAction::TogglePause);
+                        }
+                        match save::save(&ttrys, &rules) {
+                            Ok(()) => messages
+                                .push(String::from("The game is saved, resume it with --resume")),
+                            Err(message) => messages.push(message),
+                        }
+                    }
+                    leave = true;
+                    break;
+                }
+
+                let now = Instant::now();
+                let frame_due = last_draw.map_or(now, |last_draw| last_draw + frame_interval);
+                // the sprint clock runs on screen
+                let animated = display.animated()
+                    || (ttrys.options().mode == Mode::Sprint && ttrys.state() != State::Paused);
+                if (dirty || animated) && now >= frame_due {
+                    if let Some(latency) = &latency {
+                        display.set_diagnostics(latency.overlay());
+                    }
+                    display.draw(&ttrys).ok();
+                    if let Some(latency) = latency.as_mut() {
+                        latency.frame_flushed();
+                    }
+                    last_draw = Some(now);
+                    dirty = false;
+                }
+                timeline.update(&ttrys);
+
+                // wait for input until the next step, or the next frame if one is needed
+                let mut wait = timeout.remaining();
+                if dirty || animated {
+                    let frame_due = last_draw.map_or(now, |last_draw| last_draw + frame_interval);
+                    wait = wait.min(frame_due.saturating_duration_since(now));
+                }
+                if let Some(due) = autoshift.due().filter(|_| ttrys.controllable()) {
+                    wait = wait.min(due.saturating_duration_since(now));
+                }
+                if bot.is_some() {
+                    wait = wait.min(bot_due.saturating_duration_since(now));
+                }
+                // all the pending input is applied in order, before the gravity step and the
+                // next frame
+                let mut events = VecDeque::new();
+                match input.recv_timeout(wait) {
+                    Ok(event) => events.push_back(event),
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Err(RecvTimeoutError::Disconnected) => ttrys.update(UserAction::Quit),
+                }
+                events.extend(input.try_iter());
+                while let Some(event) = events.pop_front() {
+                    if !ttrys.running() || saving {
+                        break;
+                    }
+                    match event {
+                        (_, Event::Key(key)) if key.kind == KeyEventKind::Release => {
+                            autoshift.release(key.code)
+                        }
+                        (_, Event::Key(_)) if attract => ttrys.update(UserAction::Quit),
+                        (_, Event::Mouse(mouse))
+                            if attract && matches!(mouse.kind, MouseEventKind::Down(_)) =>
+                        {
+                            ttrys.update(UserAction::Quit)
+                        }
+                        // with the auto shift on, the moves repeat on their own and the other
+                        // actions not at all
+                        (_, Event::Key(key))
+                            if key.kind == KeyEventKind::Repeat && autoshift.enabled() => {}
+                        (_, Event::Key(key))
+                            if key.code == KeyCode::Tab && ttrys.state() == State::Paused =>
+                        {
+                            report_key_releases(false);
+                            // the keys pressed before are not for the settings nor the game
+                            events.clear();
+                            menu::settings(&mut display, &input, &mut choices);
+                            display.set_settings(&choices.settings, choices.theme());
+                            display.clear();
+                            autoshift =
+                                AutoShift::new(&choices.settings, choices.layout, key_releases);
+                            report_key_releases(autoshift.enabled());
+                            ttrys.set_spawn_delay(spawn_delay(&choices.settings));
+                            dirty = true;
+                        }
+                        (received, Event::Key(key)) => {
+                            // the bot has the piece to itself
+                            let action = keymap.action(key).filter(|action| {
+                                !demo
+                                    || matches!(
+                                        action,
+                                        UserAction::TogglePause
+                                            | UserAction::Quit
+                                            | UserAction::ToggleDroughtCounter
+                                            | UserAction::ToggleHelp
+                                    )
+                            });
+                            if let Some(action) = action {
+                                let action = mirror.apply(action);
+                                if matches!(action, UserAction::MoveLeft | UserAction::MoveRight) {
+                                    autoshift.press(key.code, action, received);
+                                }
+                                if action == UserAction::ToggleDroughtCounter {
+                                    display.toggle_droughts();
+                                }
+                                if action == UserAction::ToggleHelp {
+                                    display.toggle_help();
+                                }
+                                saving |= action == UserAction::Save;
+                                ttrys.update(action);
+                                display.add_events(ttrys.events());
+                                if choices.settings.sound {
+                                    audio.play(ttrys.events());
+                                }
+                                // the key ending the game has no frame to show it
+                                if let Some(latency) = latency.as_mut().filter(|_| ttrys.running())
+                                {
+                                    latency.key_received(received);
+                                }
+                                // the gravity does not run down during a pause, the countdown
+                                // ending it starts afresh
+                                if ttrys.state() == State::Paused {
+                                    timeout.pause();
+                                    autoshift.release_all();
+                                } else if action == UserAction::TogglePause
+                                    && matches!(ttrys.state(), State::Countdown(_))
+                                {
+                                    timeout = Timeout::new(ttrys.step_duration());
+                                } else {
+                                    timeout.resume();
+                                }
+                                dirty = true;
+                            }
+                        }
+                        (_, Event::Mouse(mouse)) if choices.settings.mouse && !demo => {
+                            for action in mouse_actions(mouse, &ttrys, &display) {
+                                ttrys.update(action);
+                                display.add_events(ttrys.events());
+                                if choices.settings.sound {
+                                    audio.play(ttrys.events());
+                                }
+                            }
+                            dirty = true;
+                        }
+                        _ => dirty = true,
+                    }
+                }
+
+                if !ttrys.controllable() {
+                    autoshift.charge(Instant::now());
+                } else if let Some((action, count)) = autoshift.repeat(Instant::now()) {
+                    for _ in 0..count {
+                        ttrys.update(action);
+                    }
+                    display.add_events(ttrys.events());
+                    if choices.settings.sound {
+                        audio.play(ttrys.events());
+                    }
+                    dirty = true;
+                }
+
+                if let Some(bot) = bot.as_mut().filter(|_| Instant::now() >= bot_due) {
+                    if let Some(action) = bot.next_action(&ttrys) {
+                        ttrys.update(action);
+                        display.add_events(ttrys.events());
+                        if choices.settings.sound {
+                            audio.play(ttrys.events());
+                        }
+                        dirty = true;
+                    }
+                    bot_due = Instant::now() + DEMO_INTERVAL;
+                }
+
+                if timeout.expired() {
+                    ttrys.step();
+                    display.add_events(ttrys.events());
+                    if choices.settings.sound {
+                        audio.play(ttrys.events());
+                    }
+                    let step_duration = ttrys.step_duration();
+                    timeout = Timeout::new(step_duration);
+                    dirty = true;
+                }
+            }
+            idle.store(true, Ordering::Relaxed);
+            report_key_releases(false);
+            // a request coming as the game ended found it still busy
+            leave |= terminated.load(Ordering::Relaxed);
+            if leave {
+                rank = None;
+                break;
+            }
+
+            if let Some(file) = export.as_mut() {
+                if let Err(err) = notation::write(file, &ttrys, &rules) {
+                    messages.push(format!("cannot export the game: {}", err));
+                }
+            }
+
+            if attract {
+                rank = None;
+                break;
+            }
+            let restart;
+            (restart, rank) = game_over(&mut display, &ttrys, &mut scores, &input, &mut messages);
+            if !restart {
+                break;
+            }
+            ttrys.reset();
+        }
+        if leave {
+            break;
+        }
+        if !demo {
+            last_game = Some((ttrys, timeline, scores, rank));
+        }
+    }
+    drop(terminal);
+    for message in &messages {
+        eprintln!("{}", message);
+    }
+    let Some((ttrys, timeline, scores, rank)) = last_game else {
+        return;
+    };
+    println!("Game over ! {} pts\x1b[0K", ttrys.score().1);
+    if ttrys.completed() {
+        match ttrys.options().mode {
+            Mode::Sprint => println!(
+                "Sprint finished in {}\x1b[0K",
+                format_time(ttrys.stats().played())
+            ),
+            Mode::Cheese => println!("Cheese cleared in {} pieces\x1b[0K", ttrys.stats().pieces()),
+            _ => println!("Marathon cleared !\x1b[0K"),
+        }
+    }
+    println!("Seed: {}\x1b[0K", ttrys.seed());
+
+    let extremes = |value: &dyn Fn(Tetromino) -> u32| {
+        (0..7)
+            .map(|idx| {
+                let tetro: Tetromino = idx.into();
+                format!("{} {}", tetro, value(tetro))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    println!(
+        "Longest droughts: {}\x1b[0K",
+        extremes(&|tetro| ttrys.droughts().longest(tetro))
+    );
+    println!(
+        "Worst floods (in 7 pieces): {}\x1b[0K",
+        extremes(&|tetro| ttrys.droughts().worst_flood(tetro))
+    );
+
+    let stats = ttrys.stats();
+    println!(
+        "Pieces: {}, lines: {} (singles {}, doubles {}, triples {}, tetrises {})\x1b[0K",
+        stats.pieces(),
+        stats.lines(),
+        stats.clears()[0],
+        stats.clears()[1],
+        stats.clears()[2],
+        stats.clears()[3]
+    );
+    println!(
+        "Pieces per second: {:.2} ({})\x1b[0K",
+        stats.pieces_per_second(),
+        (0..7)
+            .map(|idx| {
+                let tetro: Tetromino = idx.into();
+                format!("{} {}", tetro, stats.tetrominoes(tetro))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if stats.lines() > 0 {
+        println!(
+            "Pieces per line: {:.2}, stacking efficiency: {:.0}%\x1b[0K",
+            stats.pieces() as f32 / stats.lines() as f32,
+            100.0 * stats.stacking_efficiency()
+        );
+        println!(
+            "Burned lines: {}, tetris rate: {:.0}%\x1b[0K",
+            stats.burned_lines(),
+            100.0 * stats.tetris_rate()
+        );
+    }
+    println!("Efficiency grade: {}\x1b[0K", stats.grade());
+    timeline.print();
+    if let Some(latency) = &latency {
+        latency.print();
+    }
+
+    if let (Some(scores), Some(_)) = (&scores, rank) {
+        println!();
+        scores.print(rank);
+    }
+}
+
+// Game over panel: ask for the player name if the game makes it into the high score table, then
+// wait for the player to start a new game or quit. Return whether to restart, and the rank of the
+// game in the table. An error saving the table goes to `messages`, shown once the screen is left.
+fn game_over(
+    display: &mut GameScreen,
+    ttrys: &Ttrys,
+    scores: &mut Option<HighScores>,
+    input: &Receiver<(Instant, Event)>,
+    messages: &mut Vec<String>,
+) -> (bool, Option<usize>) {
+    let ctrl_c = |code: KeyCode, modifiers: KeyModifiers| {
+        code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL)
+    };
+
+    let (lines, score) = ttrys.score();
+    let time = ttrys.stats().played();
+    // sprints are ranked by time and cheese games by pieces, when finished
+    let (mut panel, record) = match ttrys.options().mode {
+        Mode::Classic | Mode::Marathon | Mode::Invisible => (
+            vec![
+                String::from(if ttrys.completed() {
+                    "VICTORY"
+                } else {
+                    "GAME OVER"
+                }),
+                String::new(),
+                format!("Score {}", score),
+                format!("Lines {}", lines),
+                format!("Level {}", ttrys.level()),
+            ],
+            score,
+        ),
+        Mode::Sprint if ttrys.completed() => (
+            vec![
+                String::from("FINISHED"),
+                String::new(),
+                format!("Time {}", format_time(time)),
+                format!("Pieces {}", ttrys.stats().pieces()),
+            ],
+            time.as_millis().max(1) as u32,
+        ),
+        Mode::Sprint => (
+            vec![
+                String::from("GAME OVER"),
+                String::new(),
+                format!("Time {}", format_time(time)),
+                format!("Lines {}/{}", ttrys.stats().lines(), Mode::SPRINT_LINES),
+            ],
+            0,
+        ),
+        Mode::Cheese if ttrys.completed() => (
+            vec![
+                String::from("CLEARED"),
+                String::new(),
+                format!("Pieces {}", ttrys.stats().pieces()),
+                format!("Time {}", format_time(time)),
+            ],
+            ttrys.stats().pieces().max(1),
+        ),
+        Mode::Cheese => (
+            vec![
+                String::from("GAME OVER"),
+                String::new(),
+                format!("Pieces {}", ttrys.stats().pieces()),
+                format!("Rows left {}", ttrys.lines_remaining().unwrap_or(0)),
+            ],
+            0,
+        ),
+    };
+    // how the game was lost, under the title
+    if let Some(top_out) = ttrys.top_out() {
+        panel.insert(1, top_out.to_string());
+    }
+
+    let mut rank = None;
+    if let Some(scores) = scores.as_mut().filter(|scores| scores.qualifies(record)) {
+        let mut name = String::new();
+        loop {
+            let mut prompt = panel.clone();
+            prompt.push(String::new());
+            prompt.push(String::from(match ttrys.options().mode {
+                Mode::Classic | Mode::Marathon | Mode::Invisible => "High score! Name:",
+                Mode::Sprint => "Best time! Name:",
+                Mode::Cheese => "Fewest pieces! Name:",
+            }));
+            prompt.push(format!("{}_", name));
+            display.draw_panel(ttrys, &prompt).ok();
+            match input.recv() {
+                Ok((_, Event::Key(key))) if key.kind == KeyEventKind::Release => (),
+                Ok((_, Event::Key(key))) if ctrl_c(key.code, key.modifiers) => {
+                    name.clear();
+                    break;
+                }
+                Ok((_, Event::Key(key))) => match key.code {
+                    KeyCode::Enter => break,
+                    KeyCode::Esc => {
+                        name.clear();
+                        break;
+                    }
+                    KeyCode::Backspace => {
+                        name.pop();
+                    }
+                    KeyCode::Char(c) if name.chars().count() < HighScores::MAX_NAME_LEN => {
+                        name.push(c)
+                    }
+                    _ => (),
+                },
+                Ok(_) => (),
+                Err(_) => break,
+            }
+        }
+        if name.trim().is_empty() {
+            name = std::env::var("USER").unwrap_or_default();
+        }
+        rank = scores.insert(Entry::new(&name, record, ttrys.level(), lines));
+        if let Err(message) = scores.save() {
+            messages.push(message);
+        }
+    }
+
+    panel.push(match rank {
+        Some(rank) => format!("Rank #{}", rank + 1),
+        None => String::from("Not ranked"),
+    });
+    panel.push(String::new());
+    panel.push(String::from("[R] restart"));
+    panel.push(String::from("[Q] menu"));
+    loop {
+        display.draw_panel(ttrys, &panel).ok();
+        match input.recv() {
+            Ok((_, Event::Key(key))) if key.kind == KeyEventKind::Release => (),
+            Ok((_, Event::Key(key))) if ctrl_c(key.code, key.modifiers) => return (false, rank),
+            Ok((_, Event::Key(key))) => match key.code {
+                KeyCode::Char('r' | 'R') => return (true, rank),
+                KeyCode::Char('q' | 'Q') | KeyCode::Esc => return (false, rank),
+                _ => (),
+            },
+            Ok(_) => (),
+            Err(_) => return (false, rank),
         }
     }
-    //display.clean_up();
-    println!("Game over ! {} pts\x1b[0K", ttrys.score().1);
 }
@@ -0,0 +1,686 @@
+// Main menu, shown before the games and again when one is left: the arrow keys pick an entry and
+// change its value, Enter starts the game or opens the page under the entry, Esc goes back.
+//
+// The values start from the command line, and are kept from one game to the next. The settings
+// page, also opened during a pause, saves its values to the configuration file when left.
+
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, ModifierKeyCode, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+use crossterm::style::{Attribute, Color};
+
+use ttrys::{GravityCurve, Mode, Options, RandomizerKind, Scoring, TetrominoSequence, UserAction};
+
+use crate::canvas::Canvas;
+use crate::config::{Config, Settings};
+use crate::theme::{Palette, Theme};
+use crate::{GameScreen, KeyMap, Layout, Mirror, RenderStyle, Tetromino};
+
+const MODES: [Mode; 5] = [
+    Mode::Classic,
+    Mode::Marathon,
+    Mode::Sprint,
+    Mode::Invisible,
+    Mode::Cheese,
+];
+const LAYOUTS: [(Layout, &str); 3] = [
+    (Layout::Default, "default"),
+    (Layout::OneHanded, "one-handed"),
+    (Layout::OneHandedLeft, "one-handed-left"),
+];
+const MIRRORS: [(Mirror, &str); 4] = [
+    (Mirror::None, "none"),
+    (Mirror::Input, "input"),
+    (Mirror::Board, "board"),
+    (Mirror::Both, "both"),
+];
+const RULES: [&str; 3] = ["guideline", "tgm", "classic"];
+const RANDOMIZERS: [&str; 3] = ["seven-bag", "classic", "tgm"];
+const PALETTES: [Option<Palette>; 6] = [
+    None,
+    Some(Palette::Classic),
+    Some(Palette::HighContrastDark),
+    Some(Palette::HighContrastLight),
+    Some(Palette::Pastel),
+    Some(Palette::Monochrome),
+];
+const STYLES: [RenderStyle; 3] = [
+    RenderStyle::DoubleSpace,
+    RenderStyle::HalfBlock,
+    RenderStyle::Ascii,
+];
+// delayed auto shift and auto repeat rate bounds, in ms
+const MIN_DAS: u16 = 50;
+const MAX_DAS: u16 = 500;
+const MAX_ARR: u16 = 200;
+// spawn delay bound, in ms
+const MAX_ARE: u16 = 500;
+// idle time on the main page before a demo starts
+const ATTRACT_DELAY: Duration = Duration::from_secs(30);
+// past it, the speed does not change anymore
+const MAX_LEVEL: u32 = 20;
+
+// What the menu lets the player choose
+pub struct Choices {
+    pub options: Options,
+    pub layout: Layout,
+    pub rules: String,
+    pub mirror: Mirror,
+    pub adaptive: bool,
+    pub floor_slide: bool,
+    // all games are played by the bot
+    pub demo: bool,
+    pub settings: Settings,
+    // from the configuration, along with the palette picked for the terminal, for the theme
+    pub piece_colors: [Option<Color>; 7],
+    pub detected_palette: Palette,
+    // gravity curves and scorings picked in the configuration, by mode
+    pub gravity: Vec<(Mode, GravityCurve)>,
+    pub scoring: Vec<(Mode, Scoring)>,
+}
+
+impl Choices {
+    pub fn theme(&self) -> Theme {
+        let palette = self.settings.palette.unwrap_or(self.detected_palette);
+        Theme::new(palette, self.piece_colors)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Page {
+    Main,
+    Rules,
+    Controls,
+    Settings,
+}
+
+impl Page {
+    fn title(self) -> &'static str {
+        match self {
+            Page::Main => "TTRYS",
+            Page::Rules => "RULES",
+            Page::Controls => "CONTROLS",
+            Page::Settings => "SETTINGS",
+        }
+    }
+
+    fn items(self) -> &'static [Item] {
+        match self {
+            Page::Main => &[
+                Item::Play,
+                Item::Mode,
+                Item::Level,
+                Item::Width,
+                Item::Height,
+                Item::Rules,
+                Item::Controls,
+                Item::Settings,
+                Item::Quit,
+            ],
+            Page::Rules => &[
+                Item::Rotation,
+                Item::Randomizer,
+                Item::Mirror,
+                Item::Gravity,
+                Item::FloorSlide,
+                Item::Back,
+            ],
+            Page::Controls => &[Item::Layout, Item::Back],
+            Page::Settings => &[
+                Item::Das,
+                Item::Arr,
+                Item::SpawnDelay,
+                Item::Mouse,
+                Item::Ghost,
+                Item::Theme,
+                Item::Previews,
+                Item::DangerMargin,
+                Item::Style,
+                Item::Sound,
+                Item::Back,
+            ],
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Item {
+    Play,
+    Mode,
+    Level,
+    Width,
+    Height,
+    Rules,
+    Controls,
+    Settings,
+    Quit,
+    Layout,
+    Rotation,
+    Randomizer,
+    Mirror,
+    Gravity,
+    FloorSlide,
+    Das,
+    Arr,
+    SpawnDelay,
+    Mouse,
+    Ghost,
+    Theme,
+    Previews,
+    DangerMargin,
+    Style,
+    Sound,
+    Back,
+}
+
+impl Item {
+    fn label(self) -> &'static str {
+        match self {
+            Item::Play => "Play",
+            Item::Mode => "Mode",
+            Item::Level => "Level",
+            Item::Width => "Width",
+            Item::Height => "Height",
+            Item::Rules => "Rules...",
+            Item::Controls => "Controls...",
+            Item::Settings => "Settings...",
+            Item::Quit => "Quit",
+            Item::Layout => "Layout",
+            Item::Rotation => "Rotation",
+            Item::Randomizer => "Randomizer",
+            Item::Mirror => "Mirror",
+            Item::Gravity => "Gravity",
+            Item::FloorSlide => "Floor slide",
+            Item::Das => "Auto shift",
+            Item::Arr => "Repeat rate",
+            Item::SpawnDelay => "Spawn delay",
+            Item::Mouse => "Mouse play",
+            Item::Ghost => "Ghost piece",
+            Item::Theme => "Theme",
+            Item::Previews => "Previews",
+            Item::DangerMargin => "Danger zone",
+            Item::Style => "Blocks",
+            Item::Sound => "Sound",
+            Item::Back => "Back",
+        }
+    }
+
+    // Current value, for the entries having one
+    fn value(self, choices: &Choices) -> Option<String> {
+        let on_off = |on| String::from(if on { "on" } else { "off" });
+        let settings = &choices.settings;
+        match self {
+            Item::Mode => Some(choices.options.mode.to_string()),
+            Item::Level => Some(choices.options.level.to_string()),
+            Item::Width => Some(choices.options.width.to_string()),
+            Item::Height => Some(choices.options.height.to_string()),
+            Item::Layout => Some(name_of(&LAYOUTS, choices.layout)),
+            Item::Rotation => Some(choices.rules.clone()),
+            Item::Randomizer => Some(choices.options.randomizer.to_string()),
+            Item::Mirror => Some(name_of(&MIRRORS, choices.mirror)),
+            Item::Gravity => Some(String::from(if choices.adaptive {
+                "adaptive"
+            } else {
+                "level"
+            })),
+            Item::FloorSlide => Some(on_off(choices.floor_slide)),
+            // the layout may tune the auto shift left to the terminal
+            Item::Das => Some(match (settings.das, choices.layout.auto_shift()) {
+                (Some(das), _) => format!("{} ms", das),
+                (None, Some((das, _))) => format!("{} ms (layout)", das),
+                (None, None) => String::from("terminal"),
+            }),
+            Item::Arr => Some(match (settings.das, choices.layout.auto_shift()) {
+                (None, Some((_, arr))) => format!("{} ms (layout)", arr),
+                _ => format!("{} ms", settings.arr),
+            }),
+            Item::SpawnDelay => Some(match settings.spawn_delay {
+                Some(delay) => format!("{} ms", delay),
+                None => String::from("gravity"),
+            }),
+            Item::Mouse => Some(on_off(settings.mouse)),
+            Item::Ghost => Some(on_off(settings.ghost)),
+            Item::Theme => Some(String::from(settings.palette.map_or("auto", Palette::name))),
+            Item::Previews => Some(settings.preview_count.to_string()),
+            Item::DangerMargin => Some(format!("{} rows", settings.danger_margin)),
+            Item::Style => Some(String::from(settings.style.name())),
+            Item::Sound => Some(on_off(settings.sound)),
+            _ => None,
+        }
+    }
+
+    // Step the value to the next one (`delta` 1) or the previous one (`delta` -1)
+    fn change(self, choices: &mut Choices, delta: i32) {
+        let step = |value: usize, min: usize, max: usize| {
+            value.saturating_add_signed(delta as isize).clamp(min, max)
+        };
+        // the delays go by steps of 10 ms
+        let step_ms = |value: u16, min: u16, max: u16| {
+            (value as i32 + 10 * delta).clamp(min as i32, max as i32) as u16
+        };
+        let options = &mut choices.options;
+        let settings = &mut choices.settings;
+        match self {
+            Item::Mode => options.mode = cycle(&MODES, &options.mode, delta),
+            Item::Level => {
+                options.level = step(options.level as usize, 0, MAX_LEVEL as usize) as u32
+            }
+            Item::Width => {
+                options.width = step(options.width, Options::MIN_SIZE, Options::MAX_SIZE)
+            }
+            Item::Height => {
+                options.height = step(options.height, Options::MIN_SIZE, Options::MAX_SIZE)
+            }
+            Item::Layout => choices.layout = cycle_named(&LAYOUTS, choices.layout, delta),
+            Item::Rotation => choices.rules = cycle(&RULES, &choices.rules, delta).to_string(),
+            Item::Randomizer => {
+                let name = cycle(&RANDOMIZERS, &options.randomizer.to_string(), delta);
+                if let Some(randomizer) = RandomizerKind::from_name(name) {
+                    options.randomizer = randomizer;
+                }
+            }
+            Item::Mirror => choices.mirror = cycle_named(&MIRRORS, choices.mirror, delta),
+            Item::Gravity => choices.adaptive = !choices.adaptive,
+            Item::FloorSlide => choices.floor_slide = !choices.floor_slide,
+            // going below the shortest delay leaves the repeat to the terminal
+            Item::Das => {
+                settings.das = match settings.das {
+                    None if delta > 0 => Some(MIN_DAS),
+                    None => None,
+                    Some(MIN_DAS) if delta < 0 => None,
+                    Some(das) => Some(step_ms(das, MIN_DAS, MAX_DAS)),
+                }
+            }
+            Item::Arr => settings.arr = step_ms(settings.arr, 0, MAX_ARR),
+            // going below no delay waits for the gravity
+            Item::SpawnDelay => {
+                settings.spawn_delay = match settings.spawn_delay {
+                    None if delta > 0 => Some(0),
+                    None => None,
+                    Some(0) if delta < 0 => None,
+                    Some(delay) => Some(step_ms(delay, 0, MAX_ARE)),
+                }
+            }
+            Item::Mouse => settings.mouse = !settings.mouse,
+            Item::Ghost => settings.ghost = !settings.ghost,
+            Item::Theme => settings.palette = cycle(&PALETTES, &settings.palette, delta),
+            Item::Previews => {
+                settings.preview_count =
+                    step(settings.preview_count, 0, TetrominoSequence::MAX_PREVIEW)
+            }
+            Item::DangerMargin => {
+                settings.danger_margin =
+                    step(settings.danger_margin, 0, Settings::MAX_DANGER_MARGIN)
+            }
+            Item::Style => settings.style = cycle(&STYLES, &settings.style, delta),
+            Item::Sound => settings.sound = !settings.sound,
+            _ => (),
+        }
+    }
+}
+
+// The value after (or before) `current` in `values`, wrapping around. A value missing from the list
+// (set on the command line) is followed by the first one.
+fn cycle<T: PartialEq + Copy, U: PartialEq<T> + ?Sized>(
+    values: &[T],
+    current: &U,
+    delta: i32,
+) -> T {
+    let index = match values.iter().position(|value| current == value) {
+        Some(index) => (index as i32 + delta).rem_euclid(values.len() as i32) as usize,
+        None => 0,
+    };
+    values[index]
+}
+
+fn name_of<T: PartialEq>(values: &[(T, &str)], current: T) -> String {
+    values
+        .iter()
+        .find(|(value, _)| *value == current)
+        .map_or("", |(_, name)| name)
+        .to_string()
+}
+
+fn cycle_named<T: PartialEq + Copy>(values: &[(T, &str)], current: T, delta: i32) -> T {
+    let values: Vec<T> = values.iter().map(|&(value, _)| value).collect();
+    cycle(&values, &current, delta)
+}
+
+// How the menu was left for a game
+#[derive(Copy, Clone, PartialEq)]
+pub enum Start {
+    Game,
+    // the menu was left alone, for a game played by the bot until a key is pressed
+    Demo,
+}
+
+enum Exit {
+    Play,
+    Demo,
+    Quit,
+    Back,
+}
+
+// Show the menu until the player starts a game, or a demo starts, None if the player quits
+pub fn run(
+    display: &mut GameScreen,
+    input: &Receiver<(Instant, Event)>,
+    choices: &mut Choices,
+) -> Option<Start> {
+    match show(display, input, choices, Page::Main) {
+        Exit::Play => Some(Start::Game),
+        Exit::Demo => Some(Start::Demo),
+        Exit::Quit | Exit::Back => None,
+    }
+}
+
+// Show the settings page alone, as opened during a pause
+pub fn settings(
+    display: &mut GameScreen,
+    input: &Receiver<(Instant, Event)>,
+    choices: &mut Choices,
+) {
+    show(display, input, choices, Page::Settings);
+}
+
+// Navigate from the `top` page, until a game is started or `top` is left
+fn show(
+    display: &mut GameScreen,
+    input: &Receiver<(Instant, Event)>,
+    choices: &mut Choices,
+    top: Page,
+) -> Exit {
+    let mut page = top;
+    let mut cursor = 0;
+    // settings as last saved, to write only the ones changed since, and the error of the last save
+    let mut saved = choices.settings.clone();
+    let mut error = None;
+    loop {
+        draw(display, page, cursor, choices, error.as_deref()).ok();
+        // the main page left alone for a while plays a demo
+        let event = if page == Page::Main {
+            input.recv_timeout(ATTRACT_DELAY)
+        } else {
+            input.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        };
+        let key = match event {
+            Ok((_, Event::Key(key))) if key.kind != KeyEventKind::Release => key,
+            Ok((_, Event::Mouse(mouse))) => match mouse_key(page, cursor, choices, mouse) {
+                Some((index, code)) => {
+                    cursor = index;
+                    KeyEvent::new(code, KeyModifiers::NONE)
+                }
+                None => continue,
+            },
+            // redraw, the terminal may have been resized
+            Ok(_) => continue,
+            Err(RecvTimeoutError::Timeout) => return Exit::Demo,
+            Err(RecvTimeoutError::Disconnected) => return Exit::Quit,
+        };
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Exit::Quit;
+        }
+
+        let items = page.items();
+        let mut leave = false;
+        match key.code {
+            KeyCode::Up => cursor = (cursor + items.len() - 1) % items.len(),
+            KeyCode::Down => cursor = (cursor + 1) % items.len(),
+            KeyCode::Left => items[cursor].change(choices, -1),
+            KeyCode::Right => items[cursor].change(choices, 1),
+            KeyCode::Enter | KeyCode::Char(' ') => match items[cursor] {
+                Item::Play => return Exit::Play,
+                Item::Quit => return Exit::Quit,
+                Item::Rules => (page, cursor) = (Page::Rules, 0),
+                Item::Controls => (page, cursor) = (Page::Controls, 0),
+                Item::Settings => (page, cursor) = (Page::Settings, 0),
+                Item::Back => leave = true,
+                item => item.change(choices, 1),
+            },
+            KeyCode::Esc | KeyCode::Char('q' | 'Q') => leave = true,
+            _ => (),
+        }
+        if !leave {
+            continue;
+        }
+
+        // the settings are saved on the way out, an error keeps the page open to show it
+        if page == Page::Settings && choices.settings != saved {
+            let before = std::mem::replace(&mut saved, choices.settings.clone());
+            if let Err(message) = Config::save_settings(&before, &saved) {
+                error = Some(message);
+                continue;
+            }
+        }
+        error = None;
+        if page == top {
+            return if top == Page::Main {
+                Exit::Quit
+            } else {
+                Exit::Back
+            };
+        }
+        // back on the main page, on the entry of the page left
+        let entry = match page {
+            Page::Rules => Item::Rules,
+            Page::Controls => Item::Controls,
+            _ => Item::Settings,
+        };
+        cursor = Page::Main
+            .items()
+            .iter()
+            .position(|&item| item == entry)
+            .unwrap_or(0);
+        page = Page::Main;
+    }
+}
+
+fn draw(
+    display: &mut GameScreen,
+    page: Page,
+    cursor: usize,
+    choices: &Choices,
+    error: Option<&str>,
+) -> crossterm::Result<()> {
+    let mut screen = display.begin_frame()?;
+    render(screen.canvas(), page, cursor, choices, error);
+    display.end_frame(screen)
+}
+
+// The entry under the mouse and the key the mouse stands for: a left click confirms the entry, a
+// right click steps its value back, the wheel moves the selection
+fn mouse_key(
+    page: Page,
+    cursor: usize,
+    choices: &Choices,
+    mouse: MouseEvent,
+) -> Option<(usize, KeyCode)> {
+    let (rows, _) = item_rows(page, choices);
+    let under = rows.iter().position(|&row| row == mouse.row);
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => Some((under?, KeyCode::Enter)),
+        MouseEventKind::Down(MouseButton::Right) => Some((under?, KeyCode::Left)),
+        MouseEventKind::ScrollUp => Some((cursor, KeyCode::Up)),
+        MouseEventKind::ScrollDown => Some((cursor, KeyCode::Down)),
+        _ => None,
+    }
+}
+
+// Screen rows of the entries of a page, and the first row below them
+fn item_rows(page: Page, choices: &Choices) -> (Vec<u16>, u16) {
+    let mut rows = Vec::new();
+    let mut row = 3;
+    for item in page.items() {
+        rows.push(row);
+        row += 1;
+        // the keys of the layout are listed under it
+        if *item == Item::Layout {
+            row += bindings(&KeyMap::new(choices.layout)).len() as u16 + 2;
+        }
+    }
+    (rows, row)
+}
+
+fn render(canvas: &mut Canvas, page: Page, cursor: usize, choices: &Choices, error: Option<&str>) {
+    const LEFT: u16 = 4;
+    const VALUE_COLUMN: u16 = LEFT + 16;
+
+    canvas.clear();
+    canvas.move_to(LEFT, 1);
+    canvas.set_attribute(Attribute::Bold);
+    canvas.print(page.title());
+    canvas.set_attribute(Attribute::Reset);
+
+    let (rows, mut row) = item_rows(page, choices);
+    for (index, item) in page.items().iter().enumerate() {
+        let row = rows[index];
+        let selected = index == cursor;
+        canvas.move_to(LEFT - 2, row);
+        if selected {
+            canvas.set_foreground(Color::Yellow);
+            canvas.set_attribute(Attribute::Bold);
+            canvas.print("> ");
+        } else {
+            canvas.print("  ");
+        }
+        canvas.print(item.label());
+        if let Some(value) = item.value(choices) {
+            canvas.move_to(VALUE_COLUMN, row);
+            if selected {
+                canvas.print(format!("< {} >", value));
+            } else {
+                canvas.print(format!("  {}", value));
+            }
+        }
+        canvas.set_attribute(Attribute::Reset);
+
+        // the keys of the layout are listed under it
+        if *item == Item::Layout {
+            let bindings = bindings(&KeyMap::new(choices.layout));
+            for (line, (keys, action)) in (row + 2..).zip(bindings) {
+                canvas.move_to(LEFT + 2, line);
+                canvas.set_attribute(Attribute::Dim);
+                canvas.print(action_name(action));
+                canvas.set_attribute(Attribute::Reset);
+                canvas.move_to(VALUE_COLUMN + 2, line);
+                canvas.print(keys);
+            }
+        }
+    }
+
+    // the settings are shown at work on a sample of the pieces
+    if page == Page::Settings {
+        row += 1;
+        render_preview(canvas, LEFT, row, choices);
+        row += 4;
+    }
+
+    canvas.move_to(LEFT, row + 1);
+    canvas.set_attribute(Attribute::Dim);
+    canvas.print("[Up/Down] select  [Left/Right] change  [Enter] confirm  [Esc] back");
+    canvas.set_attribute(Attribute::Reset);
+    if let Some(error) = error {
+        canvas.move_to(LEFT, row + 3);
+        canvas.set_foreground(Color::Red);
+        canvas.print(error);
+        canvas.reset_color();
+    }
+}
+
+// The seven pieces in the colors of the theme, the upcoming ones as many as previewed, and a T
+// with its ghost beside it when the ghost piece is on, in the render style
+fn render_preview(canvas: &mut Canvas, column: u16, row: u16, choices: &Choices) {
+    let theme = choices.theme();
+    let settings = &choices.settings;
+    canvas.move_to(column, row);
+    for idx in 0..7 {
+        canvas.set_background(theme.tetro_color(Tetromino::from(idx)));
+        canvas.print("  ");
+        canvas.reset_color();
+        canvas.print(" ");
+    }
+    canvas.move_to(column + 22, row);
+    canvas.print(format!("Next {}", "[]".repeat(settings.preview_count)));
+
+    // a T drawn as on the board
+    let t = theme.tetro_color(Tetromino::T);
+    let ghost = match settings.style {
+        RenderStyle::HalfBlock => GameScreen::HALF_BLOCK_GHOST,
+        RenderStyle::DoubleSpace | RenderStyle::Ascii => theme.ghost_color(Tetromino::T),
+    };
+    let pieces = [(column, t, false), (column + 10, ghost, true)];
+    for (column, color, ghost) in pieces.into_iter().take(if settings.ghost { 2 } else { 1 }) {
+        match settings.style {
+            RenderStyle::DoubleSpace if !ghost => {
+                canvas.set_background(color);
+                canvas.move_to(column, row + 1);
+                canvas.print("      ");
+                canvas.move_to(column + 2, row + 2);
+                canvas.print("  ");
+            }
+            RenderStyle::DoubleSpace | RenderStyle::Ascii => {
+                let text = if ghost && settings.style == RenderStyle::Ascii {
+                    "::"
+                } else {
+                    "[]"
+                };
+                canvas.set_foreground(color);
+                canvas.move_to(column, row + 1);
+                canvas.print(text.repeat(3));
+                canvas.move_to(column + 2, row + 2);
+                canvas.print(text);
+            }
+            RenderStyle::HalfBlock => {
+                canvas.set_foreground(color);
+                canvas.move_to(column, row + 1);
+                canvas.print("▀█▀");
+            }
+        }
+        canvas.reset_color();
+    }
+}
+
+// The keys bound to each action, in the order of the layout
+pub fn bindings(keymap: &KeyMap) -> Vec<(String, UserAction)> {
+    let mut bindings: Vec<(String, UserAction)> = Vec::new();
+    for &(code, action) in &keymap.bindings {
+        let name = match code {
+            KeyCode::Char(' ') => String::from("Space"),
+            KeyCode::Char(c) => c.to_ascii_uppercase().to_string(),
+            KeyCode::Modifier(ModifierKeyCode::LeftControl | ModifierKeyCode::RightControl) => {
+                String::from("Ctrl")
+            }
+            KeyCode::F(number) => format!("F{}", number),
+            code => format!("{:?}", code),
+        };
+        // both Ctrl keys go by the same name
+        match bindings.iter_mut().find(|(_, other)| *other == action) {
+            Some((keys, _)) if keys.split(", ").any(|key| key == name) => (),
+            Some((keys, _)) => *keys += &format!(", {}", name),
+            None => bindings.push((name, action)),
+        }
+    }
+    bindings
+}
+
+pub fn action_name(action: UserAction) -> &'static str {
+    match action {
+        UserAction::MoveLeft => "Move left",
+        UserAction::MoveRight => "Move right",
+        UserAction::RotateCW => "Rotate right",
+        UserAction::RotateCCW => "Rotate left",
+        UserAction::Rotate180 => "Rotate 180",
+        UserAction::HardDrop => "Hard drop",
+        UserAction::SoftDrop => "Soft drop",
+        UserAction::Hold => "Hold",
+        UserAction::TogglePause => "Pause",
+        UserAction::ToggleDroughtCounter => "Droughts",
+        UserAction::ToggleHelp => "Key help",
+        UserAction::Quit => "Quit",
+        UserAction::Save => "Save and quit",
+    }
+}
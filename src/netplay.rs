@@ -0,0 +1,455 @@
+// Network versus: `ttrys --host` waits for an opponent, `ttrys --connect <address>` joins. Each
+// player runs their own game, the lines cleared are sent to the other as garbage rows and the board
+// is sent after each change, to be shown next to the opponent's own.
+//
+// The protocol is line based, in plain text over TCP. The host opens with the settings of the
+// game, which the guest takes over so both get the same pieces:
+//
+//     ttrys 1 <seed> <width> <height> <level> <randomizer> <rules>
+//
+// and the guest answers "ttrys 1". Then each side sends, as it goes:
+//
+//     garbage <rows>
+//     board <width> <height> <cells>    one character per cell, from the bottom row up
+//     over                               topped out
+//     quit                               left the game
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{Event, KeyCode};
+
+use ttrys::{
+    GameEvent, Mino, Mode, Options, RandomizerKind, Scoring, Tetromino, Ttrys, UserAction,
+};
+
+use crate::{spawn_input, GameScreen, KeyMap, TerminalGuard, Timeout};
+
+pub const DEFAULT_PORT: u16 = 7373;
+const GREETING: &str = "ttrys 1";
+
+// Longest line a message can take, the board of the largest size
+const MAX_LINE: usize = "board 100 100 ".len() + Options::MAX_SIZE * Options::MAX_SIZE;
+
+// A snapshot of a board, as sent to the opponent
+pub struct Board {
+    pub width: usize,
+    pub height: usize,
+    // rows of `width` cells, from the bottom up
+    pub cells: Vec<Mino>,
+}
+
+impl Board {
+    // The stack with the falling piece in it
+    fn of(ttrys: &Ttrys) -> Self {
+        let (width, height) = (ttrys.width(), ttrys.height());
+        let mut cells: Vec<Mino> = (0..width * height)
+            .map(|idx| ttrys.mino(idx % width, idx / width))
+            .collect();
+        if let Some((tetro, minos)) = ttrys.piece() {
+            for (col, row) in minos {
+                if let Some(cell) = cells.get_mut(row as usize * width + col as usize) {
+                    *cell = Mino::Occupied(tetro);
+                }
+            }
+        }
+        Board {
+            width,
+            height,
+            cells,
+        }
+    }
+}
+
+enum Message {
+    Garbage(usize),
+    Board(Board),
+    Over,
+    Quit,
+}
+
+impl Message {
+    fn encode(&self) -> String {
+        match self {
+            Message::Garbage(rows) => format!("garbage {}", rows),
+            Message::Board(board) => {
+                let cells: String = board
+                    .cells
+                    .iter()
+                    .map(|cell| match cell {
+                        Mino::Free => '.',
+                        Mino::Occupied(tetro) => tetro.to_string().chars().next().unwrap_or('?'),
+                        Mino::Garbage => 'G',
+                        Mino::PendingClear => '*',
+                    })
+                    .collect();
+                format!("board {} {} {}", board.width, board.height, cells)
+            }
+            Message::Over => String::from("over"),
+            Message::Quit => String::from("quit"),
+        }
+    }
+
+    fn decode(line: &str) -> Option<Message> {
+        let mut words = line.split_whitespace();
+        match words.next()? {
+            // no more than a tetris can send
+            "garbage" => Some(Message::Garbage(
+                words.next()?.parse::<usize>().ok()?.min(Ttrys::MAX_GARBAGE),
+            )),
+            "board" => {
+                let sizes = Options::MIN_SIZE..=Options::MAX_SIZE;
+                let width: usize = words.next()?.parse().ok()?;
+                let height: usize = words.next()?.parse().ok()?;
+                if !sizes.contains(&width) || !sizes.contains(&height) {
+                    return None;
+                }
+                let cells = words
+                    .next()?
+                    .chars()
+                    .map(|c| match c {
+                        '.' => Some(Mino::Free),
+                        'G' => Some(Mino::Garbage),
+                        '*' => Some(Mino::PendingClear),
+                        _ => "IJLOSTZ"
+                            .find(c)
+                            .map(|idx| Mino::Occupied(Tetromino::from(idx))),
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                (Some(cells.len()) == width.checked_mul(height)).then_some(Message::Board(Board {
+                    width,
+                    height,
+                    cells,
+                }))
+            }
+            "over" => Some(Message::Over),
+            "quit" => Some(Message::Quit),
+            _ => None,
+        }
+    }
+}
+
+// Connection to the opponent, the messages are read on a thread of their own
+pub struct Link {
+    stream: TcpStream,
+    incoming: Receiver<Message>,
+}
+
+impl Link {
+    fn new(stream: TcpStream, reader: BufReader<TcpStream>) -> Self {
+        stream.set_nodelay(true).ok();
+        let (sender, incoming) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut reader = reader;
+            let mut line = Vec::new();
+            loop {
+                // a line longer than any message ends the connection
+                line.clear();
+                match (&mut reader)
+                    .take(MAX_LINE as u64 + 1)
+                    .read_until(b'\n', &mut line)
+                {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) if line.len() > MAX_LINE && line.last() != Some(&b'\n') => break,
+                    Ok(_) => (),
+                }
+                // lines that can't be understood are skipped
+                let Ok(line) = std::str::from_utf8(&line) else {
+                    continue;
+                };
+                if let Some(message) = Message::decode(line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Link { stream, incoming }
+    }
+
+    // A lost connection shows when receiving
+    fn send(&mut self, message: &Message) {
+        writeln!(self.stream, "{}", message.encode()).ok();
+    }
+}
+
+// Wait for an opponent, and send them the settings of the game. Return the link along with the
+// options, where the seed is now fixed.
+pub fn host(port: u16, options: &Options, rules: &str) -> Result<(Link, Options), String> {
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .map_err(|err| format!("cannot listen on port {}: {}", port, err))?;
+    println!("Waiting for an opponent on port {}...", port);
+    let (stream, address) = listener
+        .accept()
+        .map_err(|err| format!("cannot accept the opponent: {}", err))?;
+    let error = |err: std::io::Error| format!("{}: {}", address, err);
+
+    let options = Options {
+        seed: Some(options.seed.unwrap_or_else(rand::random)),
+        ..options.clone()
+    };
+    let mut reader = BufReader::new(stream.try_clone().map_err(error)?);
+    writeln!(
+        &stream,
+        "{} {} {} {} {} {} {}",
+        GREETING,
+        options.seed.unwrap_or_default(),
+        options.width,
+        options.height,
+        options.level,
+        options.randomizer,
+        rules
+    )
+    .map_err(error)?;
+    let mut answer = String::new();
+    reader.read_line(&mut answer).map_err(error)?;
+    if answer.trim() != GREETING {
+        return Err(format!("{}: not a ttrys player", address));
+    }
+    Ok((Link::new(stream, reader), options))
+}
+
+// Join a game, taking over the settings of the host. Return the link, the options and the rules.
+pub fn connect(address: &str) -> Result<(Link, Options, String), String> {
+    let stream = if address.contains(':') {
+        TcpStream::connect(address)
+    } else {
+        TcpStream::connect((address, DEFAULT_PORT))
+    }
+    .map_err(|err| format!("cannot connect to {}: {}", address, err))?;
+    let error = |err: std::io::Error| format!("{}: {}", address, err);
+
+    let mut reader = BufReader::new(stream.try_clone().map_err(error)?);
+    let mut greeting = String::new();
+    reader.read_line(&mut greeting).map_err(error)?;
+    let settings = greeting
+        .trim()
+        .strip_prefix(GREETING)
+        .map(|settings| settings.split_whitespace().collect::<Vec<_>>());
+    let (options, rules) = match settings.as_deref() {
+        Some([seed, width, height, level, randomizer, rules]) => {
+            let options = (|| {
+                Some(Options {
+                    seed: Some(seed.parse().ok()?),
+                    width: width.parse().ok()?,
+                    height: height.parse().ok()?,
+                    level: level.parse().ok()?,
+                    randomizer: RandomizerKind::from_name(randomizer)?,
+                    // network games are classic ones
+                    mode: Mode::Classic,
+                    endless: false,
+                    gravity: None,
+                    scoring: Scoring::Ttrys,
+                })
+            })();
+            (options, rules.to_string())
+        }
+        _ => (None, String::new()),
+    };
+    let options = options.ok_or_else(|| format!("{}: not a ttrys host", address))?;
+    writeln!(&stream, "{}", GREETING).map_err(error)?;
+    Ok((Link::new(stream, reader), options, rules))
+}
+
+enum Outcome {
+    Won,
+    Lost,
+    OpponentLeft,
+    Quit,
+}
+
+pub fn run(
+    mut ttrys: Ttrys,
+    mut display: GameScreen,
+    mut link: Link,
+    keymap: KeyMap,
+    frame_interval: Duration,
+) {
+    let terminal = TerminalGuard::new();
+    let input = spawn_input();
+
+    // the game can't be paused, the opponent's keeps going
+    let mut opponent: Option<Board> = None;
+    let mut outcome = None;
+    let mut timeout = Timeout::new(Duration::default());
+    let mut last_draw: Option<Instant> = None;
+    let mut last_sent = None;
+    let mut dirty = true;
+    while outcome.is_none() {
+        let now = Instant::now();
+        let frame_due = last_draw.map_or(now, |last_draw| last_draw + frame_interval);
+        if (dirty || display.animated()) && now >= frame_due {
+            display.draw_with_opponent(&ttrys, opponent.as_ref()).ok();
+            // the board is only sent when it changed
+            let hash = ttrys.state_hash();
+            if last_sent != Some(hash) {
+                link.send(&Message::Board(Board::of(&ttrys)));
+                last_sent = Some(hash);
+            }
+            last_draw = Some(now);
+            dirty = false;
+        }
+
+        // the messages of the opponent are checked at least once per frame interval
+        let mut wait = timeout.remaining().min(frame_interval);
+        if dirty || display.animated() {
+            wait = wait.min(frame_due.saturating_duration_since(now));
+        }
+        match input.recv_timeout(wait) {
+            Ok((_, Event::Key(key))) => match keymap.action(key) {
+                Some(UserAction::Quit) => outcome = Some(Outcome::Quit),
+                Some(UserAction::TogglePause) | None => (),
+                Some(action) => {
+                    if action == UserAction::ToggleDroughtCounter {
+                        display.toggle_droughts();
+                    }
+                    ttrys.update(action);
+                    display.add_events(ttrys.events());
+                    dirty = true;
+                }
+            },
+            Ok(_) => dirty = true,
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => outcome = Some(Outcome::Quit),
+        }
+
+        loop {
+            match link.incoming.try_recv() {
+                Ok(Message::Garbage(rows)) => ttrys.add_garbage(rows),
+                // both play on the size the host set
+                Ok(Message::Board(board)) => {
+                    if (board.width, board.height) == (ttrys.width(), ttrys.height()) {
+                        opponent = Some(board);
+                    }
+                }
+                Ok(Message::Over) => outcome = Some(Outcome::Won),
+                Ok(Message::Quit) | Err(TryRecvError::Disconnected) => {
+                    outcome.get_or_insert(Outcome::OpponentLeft);
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+            dirty = true;
+            if outcome.is_some() {
+                break;
+            }
+        }
+
+        if timeout.expired() && outcome.is_none() {
+            ttrys.step();
+            display.add_events(ttrys.events());
+            for event in ttrys.events() {
+                if let GameEvent::LinesCleared { rows, .. } = *event {
+                    let garbage = Ttrys::garbage_for(rows);
+                    if garbage > 0 {
+                        link.send(&Message::Garbage(garbage));
+                    }
+                }
+            }
+            timeout = Timeout::new(ttrys.step_duration());
+            dirty = true;
+            if !ttrys.running() {
+                link.send(&Message::Over);
+                outcome = Some(Outcome::Lost);
+            }
+        }
+    }
+
+    let title = match outcome {
+        Some(Outcome::Won) => "YOU WIN",
+        Some(Outcome::Lost) => "GAME OVER",
+        Some(Outcome::OpponentLeft) => "OPPONENT LEFT",
+        Some(Outcome::Quit) | None => {
+            link.send(&Message::Quit);
+            ""
+        }
+    };
+    if !title.is_empty() {
+        let panel = [
+            String::from(title),
+            String::new(),
+            format!("Lines {}", ttrys.stats().lines()),
+            String::new(),
+            String::from("[Q] quit"),
+        ];
+        display.draw_panel(&ttrys, &panel).ok();
+        while let Ok(event) = input.recv() {
+            if let (_, Event::Key(key)) = event {
+                if matches!(key.code, KeyCode::Char('q' | 'Q') | KeyCode::Esc)
+                    || keymap.action(key) == Some(UserAction::Quit)
+                {
+                    break;
+                }
+            }
+        }
+    }
+    drop(terminal);
+    println!(
+        "{}",
+        if title.is_empty() {
+            "Left the game"
+        } else {
+            title
+        }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The message decoded from the line, encoded again
+    fn round_trip(line: &str) -> Option<String> {
+        Message::decode(line).map(|message| message.encode())
+    }
+
+    #[test]
+    fn messages_decode_as_they_were_encoded() {
+        for line in ["garbage 2", "over", "quit", "board 4 4 IJLOSTZG*......."] {
+            assert_eq!(round_trip(line).as_deref(), Some(line));
+        }
+
+        let board = Board {
+            width: 10,
+            height: 20,
+            cells: (0..200)
+                .map(|idx| match idx % 10 {
+                    0..=6 => Mino::Occupied(Tetromino::from(idx % 10)),
+                    7 => Mino::Garbage,
+                    8 => Mino::PendingClear,
+                    _ => Mino::Free,
+                })
+                .collect(),
+        };
+        let line = Message::Board(board).encode();
+        assert_eq!(round_trip(&line), Some(line));
+    }
+
+    #[test]
+    fn garbage_is_bounded() {
+        assert_eq!(round_trip("garbage 1000000").as_deref(), Some("garbage 4"));
+        assert!(Message::decode("garbage 100000000000000000000000").is_none());
+        assert!(Message::decode("garbage -1").is_none());
+    }
+
+    #[test]
+    fn malformed_boards_are_rejected() {
+        let cells = |count| ".".repeat(count);
+        for line in [
+            // too small, too large
+            format!("board 3 20 {}", cells(60)),
+            format!("board 10 3 {}", cells(30)),
+            format!("board 101 4 {}", cells(404)),
+            // a cell too many or too few
+            format!("board 4 4 {}", cells(17)),
+            format!("board 4 4 {}", cells(15)),
+            // unknown letters
+            format!("board 4 4 X{}", cells(15)),
+            format!("board 4 4 i{}", cells(15)),
+            String::from("board 4 4"),
+        ] {
+            assert!(Message::decode(&line).is_none(), "{}", line);
+        }
+    }
+}
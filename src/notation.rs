@@ -0,0 +1,40 @@
+// Game notation: the settings of a game then its placements as text, one per line, so that a run
+// can be discussed or replayed by hand:
+//
+//     # seed 1234, randomizer seven-bag, rules guideline, 10x20, level 0
+//     1 T 4 R
+//     2 I 1 0
+//
+// Each placement gives the piece letter, the leftmost column it covers (from 1, on the left) and
+// its rotation: 0 for the spawn state, R and L after a clockwise or counterclockwise turn, 2 after
+// a half turn.
+
+use std::io::Write;
+
+use ttrys::Ttrys;
+
+// Append the notation of a game, followed by a blank line
+pub fn write(out: &mut impl Write, ttrys: &Ttrys, rules: &str) -> std::io::Result<()> {
+    let options = ttrys.options();
+    writeln!(
+        out,
+        "# seed {}, randomizer {}, rules {}, {}x{}, level {}",
+        ttrys.seed(),
+        options.randomizer,
+        rules,
+        ttrys.width(),
+        ttrys.height(),
+        options.level
+    )?;
+    for (index, placement) in ttrys.placements().iter().enumerate() {
+        writeln!(
+            out,
+            "{} {} {} {}",
+            index + 1,
+            placement.tetro,
+            placement.column + 1,
+            placement.rotation
+        )?;
+    }
+    writeln!(out)
+}
@@ -0,0 +1,229 @@
+//! Rotation systems: how the pieces look in each rotation state and how they kick off walls and
+//! the stack when rotated.
+//!
+//! Mino positions are relative to the top-left corner of the piece bounding box, with x pointing
+//! right and y pointing up. Kick offsets use the same axes.
+
+use crate::{RotationState, Tetromino, Turn};
+
+/// How pieces are shaped and rotated
+pub trait RotationSystem {
+    /// Relative positions of the minos of a tetromino in the given rotation state
+    fn minos(&self, tetro: Tetromino, rotation: RotationState) -> [(i8, i8); 4];
+
+    /// Offsets tried in turn when rotating from the given state, the first one that does not
+    /// collide is used
+    fn kicks(&self, tetro: Tetromino, from: RotationState, turn: Turn) -> &'static [(i8, i8)];
+
+    /// Spawn state of a tetromino moved up against the top of its bounding box, for the previews
+    fn preview(&self, tetro: Tetromino) -> [(i8, i8); 4] {
+        let minos = self.minos(tetro, RotationState::default());
+        let top = minos.iter().map(|mino| mino.1).max().unwrap_or(0);
+        minos.map(|(x, y)| (x, y - top))
+    }
+}
+
+/// Rotation system of a rule preset: "guideline" (SRS), "tgm" (ARS) or "classic" (no kicks)
+pub fn from_preset(name: &str) -> Option<Box<dyn RotationSystem>> {
+    match name {
+        "guideline" => Some(Box::new(Srs)),
+        "tgm" => Some(Box::new(Ars)),
+        "classic" => Some(Box::new(NoKicks)),
+        _ => None,
+    }
+}
+
+fn kick_index(from: RotationState, turn: Turn) -> usize {
+    2 * usize::from(from) + if turn == Turn::Cw { 0 } else { 1 }
+}
+
+/// Super Rotation System, as in guideline Tetris
+pub struct Srs;
+
+impl RotationSystem for Srs {
+    fn minos(&self, tetro: Tetromino, rotation: RotationState) -> [(i8, i8); 4] {
+        SRS_TETROMINO_DATA[tetro as usize][usize::from(rotation)]
+    }
+
+    fn kicks(&self, tetro: Tetromino, from: RotationState, turn: Turn) -> &'static [(i8, i8)] {
+        match (tetro, turn) {
+            (Tetromino::O, _) => &[(0, 0)],
+            (_, Turn::Half) => &SRS_HALF_TURN_KICKS[usize::from(from)],
+            (Tetromino::I, _) => &SRS_I_WALL_KICKS[kick_index(from, turn)],
+            _ => &SRS_JLSTZ_WALL_KICKS[kick_index(from, turn)],
+        }
+    }
+}
+
+/// Classic rotation: SRS shapes, without any kick
+pub struct NoKicks;
+
+impl RotationSystem for NoKicks {
+    fn minos(&self, tetro: Tetromino, rotation: RotationState) -> [(i8, i8); 4] {
+        Srs.minos(tetro, rotation)
+    }
+
+    fn kicks(&self, _tetro: Tetromino, _from: RotationState, _turn: Turn) -> &'static [(i8, i8)] {
+        &[(0, 0)]
+    }
+}
+
+/// Arika Rotation System, as in the TGM series: pieces sit at the bottom of their bounding box and
+/// spawn flat side up, and kicks are limited to one column right, then left. The I piece does not
+/// kick. The TGM exceptions preventing some J, L and T kicks are not implemented.
+pub struct Ars;
+
+impl RotationSystem for Ars {
+    fn minos(&self, tetro: Tetromino, rotation: RotationState) -> [(i8, i8); 4] {
+        ARS_TETROMINO_DATA[tetro as usize][usize::from(rotation)]
+    }
+
+    fn kicks(&self, tetro: Tetromino, _from: RotationState, _turn: Turn) -> &'static [(i8, i8)] {
+        match tetro {
+            Tetromino::I => &[(0, 0)],
+            _ => &[(0, 0), (1, 0), (-1, 0)],
+        }
+    }
+}
+
+// One entry per tetromino, discribing each 4 rotation states by the relative position of the minos
+// The first state is the spawning state, and thes tates are listed clock-wise.
+const SRS_TETROMINO_DATA: [[[(i8, i8); 4]; 4]; 7] = [
+    // I
+    [
+        [(0, -1), (1, -1), (2, -1), (3, -1)],
+        [(2, 0), (2, -1), (2, -2), (2, -3)],
+        [(0, -2), (1, -2), (2, -2), (3, -2)],
+        [(1, 0), (1, -1), (1, -2), (1, -3)],
+    ],
+    // J
+    [
+        [(0, 0), (0, -1), (1, -1), (2, -1)],
+        [(1, 0), (2, 0), (1, -1), (1, -2)],
+        [(0, -1), (1, -1), (2, -1), (2, -2)],
+        [(1, 0), (1, -1), (0, -2), (1, -2)],
+    ],
+    // L
+    [
+        [(2, 0), (0, -1), (1, -1), (2, -1)],
+        [(1, 0), (1, -1), (1, -2), (2, -2)],
+        [(0, -1), (1, -1), (2, -1), (0, -2)],
+        [(0, 0), (1, 0), (1, -1), (1, -2)],
+    ],
+    // O
+    [[(1, 0), (2, 0), (1, -1), (2, -1)]; 4],
+    // S
+    [
+        [(1, 0), (2, 0), (0, -1), (1, -1)],
+        [(1, 0), (1, -1), (2, -1), (2, -2)],
+        [(1, -1), (2, -1), (0, -2), (1, -2)],
+        [(0, 0), (0, -1), (1, -1), (1, -2)],
+    ],
+    // T
+    [
+        [(1, 0), (0, -1), (1, -1), (2, -1)],
+        [(1, 0), (1, -1), (2, -1), (1, -2)],
+        [(0, -1), (1, -1), (2, -1), (1, -2)],
+        [(1, 0), (0, -1), (1, -1), (1, -2)],
+    ],
+    // Z
+    [
+        [(0, 0), (1, 0), (1, -1), (2, -1)],
+        [(2, 0), (1, -1), (2, -1), (1, -2)],
+        [(0, -1), (1, -1), (1, -2), (2, -2)],
+        [(1, 0), (0, -1), (1, -1), (0, -2)],
+    ],
+];
+
+// Super Rotation System wall kicks: the offsets tried in turn when rotating a piece, until one
+// does not collide. There is one entry per rotation, at index 2 * initial state + direction
+// (0: clock-wise, 1: counter clock-wise). The O piece does not kick.
+const SRS_JLSTZ_WALL_KICKS: [[(i8, i8); 5]; 8] = [
+    // 0 -> R, 0 -> L
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    // R -> 2, R -> 0
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    // 2 -> L, 2 -> R
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    // L -> 0, L -> 2
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+
+const SRS_I_WALL_KICKS: [[(i8, i8); 5]; 8] = [
+    // 0 -> R, 0 -> L
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    // R -> 2, R -> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    // 2 -> L, 2 -> R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    // L -> 0, L -> 2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+];
+
+// Half turn kicks, which SRS does not define: the ones of TETR.IO, the same for every piece. There
+// is one entry per initial state.
+const SRS_HALF_TURN_KICKS: [[(i8, i8); 6]; 4] = [
+    // 0 -> 2
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],
+    // R -> L
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],
+    // 2 -> 0
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)],
+    // L -> R
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)],
+];
+
+const ARS_TETROMINO_DATA: [[[(i8, i8); 4]; 4]; 7] = [
+    // I
+    [
+        [(0, -1), (1, -1), (2, -1), (3, -1)],
+        [(2, 0), (2, -1), (2, -2), (2, -3)],
+        [(0, -1), (1, -1), (2, -1), (3, -1)],
+        [(2, 0), (2, -1), (2, -2), (2, -3)],
+    ],
+    // J
+    [
+        [(0, -1), (1, -1), (2, -1), (2, -2)],
+        [(1, 0), (1, -1), (0, -2), (1, -2)],
+        [(0, -1), (0, -2), (1, -2), (2, -2)],
+        [(1, 0), (2, 0), (1, -1), (1, -2)],
+    ],
+    // L
+    [
+        [(0, -1), (1, -1), (2, -1), (0, -2)],
+        [(0, 0), (1, 0), (1, -1), (1, -2)],
+        [(2, -1), (0, -2), (1, -2), (2, -2)],
+        [(1, 0), (1, -1), (1, -2), (2, -2)],
+    ],
+    // O
+    [[(1, -1), (2, -1), (1, -2), (2, -2)]; 4],
+    // S
+    [
+        [(1, -1), (2, -1), (0, -2), (1, -2)],
+        [(0, 0), (0, -1), (1, -1), (1, -2)],
+        [(1, -1), (2, -1), (0, -2), (1, -2)],
+        [(0, 0), (0, -1), (1, -1), (1, -2)],
+    ],
+    // T
+    [
+        [(0, -1), (1, -1), (2, -1), (1, -2)],
+        [(1, 0), (0, -1), (1, -1), (1, -2)],
+        [(1, -1), (0, -2), (1, -2), (2, -2)],
+        [(1, 0), (1, -1), (2, -1), (1, -2)],
+    ],
+    // Z
+    [
+        [(0, -1), (1, -1), (1, -2), (2, -2)],
+        [(2, 0), (1, -1), (2, -1), (1, -2)],
+        [(0, -1), (1, -1), (1, -2), (2, -2)],
+        [(2, 0), (1, -1), (2, -1), (1, -2)],
+    ],
+];
@@ -0,0 +1,115 @@
+// Game left to be resumed later, stored in $XDG_DATA_HOME/ttrys/save.json
+// (~/.local/share/ttrys/save.json) and deleted once resumed.
+//
+// The game is serialized whole (stack, pieces, queue, random number generators, counters), along
+// with the rules preset giving its rotation system.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use ttrys::Ttrys;
+
+use crate::scores::data_dir;
+
+#[derive(Serialize)]
+struct SavedGameRef<'a> {
+    rules: &'a str,
+    game: &'a Ttrys,
+}
+
+#[derive(Deserialize)]
+pub struct SavedGame {
+    pub rules: String,
+    pub game: Ttrys,
+}
+
+pub fn path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("save.json"))
+}
+
+pub fn save(game: &Ttrys, rules: &str) -> Result<(), String> {
+    let path = path().ok_or("cannot locate the data directory (HOME is not set)")?;
+    let error = |err: std::io::Error| format!("{}: {}", path.display(), err);
+    let json = serde_json::to_string(&SavedGameRef { rules, game })
+        .map_err(|err| format!("cannot save the game: {}", err))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(error)?;
+    }
+    std::fs::write(&path, json).map_err(error)
+}
+
+// Take the saved game out of its file, a game is resumed only once
+pub fn resume() -> Result<SavedGame, String> {
+    let path = path().ok_or("cannot locate the data directory (HOME is not set)")?;
+    let error = |err: std::io::Error| format!("{}: {}", path.display(), err);
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err("there is no saved game to resume".to_string())
+        }
+        Err(err) => return Err(error(err)),
+    };
+    let saved =
+        serde_json::from_str(&json).map_err(|err| format!("{}: {}", path.display(), err))?;
+    std::fs::remove_file(&path).map_err(error)?;
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ttrys::bot::Bot;
+    use ttrys::{Options, Tetromino, TetrominoSequence};
+
+    // Let the bot play until the given number of pieces is locked
+    fn play(ttrys: &mut Ttrys, pieces: u32) {
+        let mut bot = Bot::new();
+        while ttrys.running() && ttrys.stats().pieces() < pieces {
+            match bot.next_action(ttrys) {
+                Some(action) => ttrys.update(action),
+                None => ttrys.step(),
+            }
+        }
+    }
+
+    #[test]
+    fn saved_games_resume_where_they_were_left() {
+        let mut game = Ttrys::new(Options {
+            seed: Some(7),
+            ..Options::default()
+        });
+        play(&mut game, 20);
+        assert!(game.running());
+        let json = serde_json::to_string(&SavedGameRef {
+            rules: "srs",
+            game: &game,
+        })
+        .unwrap();
+        let SavedGame {
+            rules,
+            game: mut resumed,
+        } = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(rules, "srs");
+        assert_eq!(resumed.state_hash(), game.state_hash());
+        let (stats, resumed_stats) = (game.stats(), resumed.stats());
+        assert_eq!(resumed_stats.pieces(), stats.pieces());
+        assert_eq!(resumed_stats.clears(), stats.clears());
+        assert_eq!(resumed_stats.played(), stats.played());
+        for idx in 0..7 {
+            let tetro = Tetromino::from(idx);
+            assert_eq!(resumed_stats.tetrominoes(tetro), stats.tetrominoes(tetro));
+        }
+
+        // the random number generators go on the same
+        play(&mut game, 40);
+        play(&mut resumed, 40);
+        assert_eq!(game.stats().pieces(), 40);
+        assert_eq!(
+            resumed.upcoming(TetrominoSequence::MAX_PREVIEW),
+            game.upcoming(TetrominoSequence::MAX_PREVIEW)
+        );
+        assert_eq!(resumed.state_hash(), game.state_hash());
+    }
+}
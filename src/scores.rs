@@ -0,0 +1,495 @@
+// High score table, stored in $XDG_DATA_HOME/ttrys/scores.toml (~/.local/share/ttrys/scores.toml)
+//
+// The file is a small subset of TOML, one table per entry, best score first:
+//
+//     [[score]]
+//     name = "alice"
+//     score = 12500
+//     level = 12
+//     lines = 98
+//     date = "2024-03-02"
+//
+// The best sprint times are kept apart in sprint.toml, with the time in milliseconds in place of
+// the score (`time_ms = 83456`), shortest first.
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crossterm::style::Stylize;
+
+use ttrys::Mode;
+
+pub struct Entry {
+    pub name: String,
+    pub score: u32,
+    pub level: u32,
+    pub lines: u32,
+    // YYYY-MM-DD, in UTC
+    pub date: String,
+}
+
+impl Entry {
+    pub fn new(name: &str, score: u32, level: u32, lines: u32) -> Self {
+        Entry {
+            name: sanitize_name(name),
+            score,
+            level,
+            lines,
+            date: today(),
+        }
+    }
+}
+
+// One table per game mode: sprints are ranked by time, cheese games by pieces, the others by
+// score
+pub struct HighScores {
+    mode: Mode,
+    entries: Vec<Entry>,
+}
+
+impl HighScores {
+    pub const MAX_ENTRIES: usize = 10;
+    pub const MAX_NAME_LEN: usize = 16;
+
+    fn new(mode: Mode) -> Self {
+        HighScores {
+            mode,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn path(mode: Mode) -> Option<PathBuf> {
+        let file = match mode {
+            Mode::Classic => "scores.toml",
+            Mode::Marathon => "marathon.toml",
+            Mode::Sprint => "sprint.toml",
+            Mode::Invisible => "invisible.toml",
+            Mode::Cheese => "cheese.toml",
+        };
+        data_dir().map(|dir| dir.join(file))
+    }
+
+    // A missing file is an empty table
+    pub fn load(mode: Mode) -> Result<HighScores, String> {
+        let Some(path) = Self::path(mode) else {
+            return Ok(HighScores::new(mode));
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                Self::parse(mode, &text).map_err(|err| format!("{}:{}", path.display(), err))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HighScores::new(mode)),
+            Err(err) => Err(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path =
+            Self::path(self.mode).ok_or("cannot locate the data directory (HOME is not set)")?;
+        let error = |err: std::io::Error| format!("{}: {}", path.display(), err);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(error)?;
+        }
+        std::fs::write(&path, self.to_toml()).map_err(error)
+    }
+
+    // Whether a score (or time, or piece count) beats another one
+    fn better(&self, score: u32, other: u32) -> bool {
+        match self.mode {
+            Mode::Classic | Mode::Marathon | Mode::Invisible => score > other,
+            Mode::Sprint | Mode::Cheese => score < other,
+        }
+    }
+
+    // Key of the score in the file
+    fn score_key(&self) -> &'static str {
+        match self.mode {
+            Mode::Classic | Mode::Marathon | Mode::Invisible => "score",
+            Mode::Sprint => "time_ms",
+            Mode::Cheese => "pieces",
+        }
+    }
+
+    // Whether a score makes it into the table
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0
+            && (self.entries.len() < Self::MAX_ENTRIES
+                || self
+                    .entries
+                    .iter()
+                    .any(|entry| self.better(score, entry.score)))
+    }
+
+    // Insert an entry at its rank, dropping the last one if the table is full. Return the rank,
+    // unless the score did not qualify.
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        // ties go to the older entry
+        let rank = self
+            .entries
+            .iter()
+            .position(|other| self.better(entry.score, other.score))
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(Self::MAX_ENTRIES);
+        Some(rank)
+    }
+
+    // Print the table, the entry at `highlight` (a fresh one) standing out
+    pub fn print(&self, highlight: Option<usize>) {
+        let (title, score_header) = match self.mode {
+            Mode::Classic => ("High scores", "Score"),
+            Mode::Marathon => ("Best marathons", "Score"),
+            Mode::Sprint => ("Best sprint times", "Time"),
+            Mode::Invisible => ("High scores (invisible)", "Score"),
+            Mode::Cheese => ("Fewest pieces to dig out", "Pieces"),
+        };
+        println!("{}\x1b[0K", title.bold());
+        if self.entries.is_empty() {
+            println!("  no scores yet\x1b[0K");
+            return;
+        }
+        println!(
+            "{}\x1b[0K",
+            format!(
+                "  {:>2}  {:<width$}  {:>8}  {:>5}  {:>5}  {:<10}",
+                "#",
+                "Name",
+                score_header,
+                "Level",
+                "Lines",
+                "Date",
+                width = Self::MAX_NAME_LEN
+            )
+            .dim()
+        );
+        for (rank, entry) in self.entries.iter().enumerate() {
+            let line = format!(
+                "  {:>2}  {:<width$}  {:>8}  {:>5}  {:>5}  {:<10}",
+                rank + 1,
+                entry.name,
+                match self.mode {
+                    Mode::Sprint => format_time(Duration::from_millis(entry.score as u64)),
+                    _ => entry.score.to_string(),
+                },
+                entry.level,
+                entry.lines,
+                entry.date,
+                width = Self::MAX_NAME_LEN
+            );
+            if highlight == Some(rank) {
+                println!("{}\x1b[0K", line.yellow().bold());
+            } else {
+                println!("{}\x1b[0K", line);
+            }
+        }
+    }
+
+    fn parse(mode: Mode, text: &str) -> Result<HighScores, String> {
+        // fields of the entry being read
+        #[derive(Default)]
+        struct Fields {
+            name: Option<String>,
+            score: Option<u32>,
+            level: Option<u32>,
+            lines: Option<u32>,
+            date: Option<String>,
+        }
+
+        impl Fields {
+            fn entry(self) -> Option<Entry> {
+                Some(Entry {
+                    name: self.name?,
+                    score: self.score?,
+                    level: self.level?,
+                    lines: self.lines?,
+                    date: self.date?,
+                })
+            }
+        }
+
+        let mut scores = HighScores::new(mode);
+        let score_key = scores.score_key();
+        let mut current: Option<(usize, Fields)> = None;
+        let mut finish = |current: Option<(usize, Fields)>| match current {
+            Some((line_number, fields)) => {
+                let entry = fields
+                    .entry()
+                    .ok_or_else(|| format!("{}: incomplete score entry", line_number + 1))?;
+                scores.entries.push(entry);
+                Ok::<(), String>(())
+            }
+            None => Ok(()),
+        };
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| format!("{}: {}", line_number + 1, message);
+            if line == "[[score]]" {
+                finish(current.replace((line_number, Fields::default())))?;
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| error(format!("expected 'key = value', got '{}'", line)))?;
+            let (_, fields) = current
+                .as_mut()
+                .ok_or_else(|| error(String::from("expected '[[score]]'")))?;
+            let string = || {
+                value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .map(String::from)
+                    .ok_or_else(|| error(format!("invalid string {}", value)))
+            };
+            let number = || {
+                value
+                    .parse()
+                    .map_err(|_| error(format!("invalid number {}", value)))
+            };
+            match key {
+                "name" => fields.name = Some(string()?),
+                key if key == score_key => fields.score = Some(number()?),
+                "level" => fields.level = Some(number()?),
+                "lines" => fields.lines = Some(number()?),
+                "date" => fields.date = Some(string()?),
+                _ => return Err(error(format!("unknown key '{}'", key))),
+            }
+        }
+        finish(current)?;
+
+        match mode {
+            Mode::Classic | Mode::Marathon | Mode::Invisible => scores
+                .entries
+                .sort_by_key(|entry| std::cmp::Reverse(entry.score)),
+            Mode::Sprint | Mode::Cheese => scores.entries.sort_by_key(|entry| entry.score),
+        }
+        scores.entries.truncate(Self::MAX_ENTRIES);
+        Ok(scores)
+    }
+
+    fn to_toml(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "[[score]]\nname = \"{}\"\n{} = {}\nlevel = {}\nlines = {}\ndate = \"{}\"\n",
+                    entry.name,
+                    self.score_key(),
+                    entry.score,
+                    entry.level,
+                    entry.lines,
+                    entry.date
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// Duration as minutes, seconds and hundredths: 1:23.45
+pub fn format_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!(
+        "{}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+// Names are kept short and free of characters needing escapes in the file
+fn sanitize_name(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .filter(|&c| !c.is_control() && c != '"' && c != '\\')
+        .take(HighScores::MAX_NAME_LEN)
+        .collect();
+    if name.is_empty() {
+        String::from("anonymous")
+    } else {
+        name
+    }
+}
+
+// Current UTC date as YYYY-MM-DD
+fn today() -> String {
+    date(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs()),
+    )
+}
+
+// UTC date as YYYY-MM-DD of a time in seconds since the epoch
+fn date(secs: u64) -> String {
+    // convert days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// $XDG_DATA_HOME/ttrys, or ~/.local/share/ttrys
+pub fn data_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .map(|dir| dir.join("ttrys"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: u32) -> Entry {
+        Entry {
+            name: String::from(name),
+            score,
+            level: 1,
+            lines: 2,
+            date: String::from("2024-03-02"),
+        }
+    }
+
+    fn names(scores: &HighScores) -> Vec<&str> {
+        scores
+            .entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn tables_are_written_as_they_were_read() {
+        let text = r#"
+# out of order
+[[score]]
+name = "bob"
+score = 900
+level = 3
+lines = 20
+date = "2024-03-01"
+
+[[score]]
+  name = "alice"
+  score = 12500
+  level = 12
+  lines = 98
+  date = "2024-03-02"
+"#;
+        let scores = HighScores::parse(Mode::Classic, text).unwrap();
+        assert_eq!(names(&scores), ["alice", "bob"]);
+        let toml = scores.to_toml();
+        assert!(toml.starts_with("[[score]]\nname = \"alice\"\nscore = 12500\nlevel = 12\n"));
+        let again = HighScores::parse(Mode::Classic, &toml).unwrap();
+        assert_eq!(again.to_toml(), toml);
+
+        // the sprint times go by another key
+        let mut sprints = HighScores::new(Mode::Sprint);
+        sprints.insert(entry("carol", 83456));
+        let toml = sprints.to_toml();
+        assert!(toml.contains("time_ms = 83456"));
+        assert_eq!(
+            HighScores::parse(Mode::Sprint, &toml).unwrap().to_toml(),
+            toml
+        );
+    }
+
+    #[test]
+    fn malformed_tables_are_rejected_with_their_line() {
+        for (text, error) in [
+            ("name = \"bob\"", "1: expected '[[score]]'"),
+            ("[[score]]\nname = bob", "2: invalid string bob"),
+            ("[[score]]\nscore = many", "2: invalid number many"),
+            ("[[score]]\ntime_ms = 1", "2: unknown key 'time_ms'"),
+            (
+                "[[score]]\nname = \"bob\"\n[[score]]",
+                "1: incomplete score entry",
+            ),
+            ("[[score]]\nname", "2: expected 'key = value', got 'name'"),
+        ] {
+            assert_eq!(
+                HighScores::parse(Mode::Classic, text).err().as_deref(),
+                Some(error)
+            );
+        }
+    }
+
+    #[test]
+    fn entries_are_ranked_by_mode() {
+        let mut scores = HighScores::new(Mode::Marathon);
+        let mut sprints = HighScores::new(Mode::Sprint);
+        for score in 1..=12 {
+            scores.insert(entry(&score.to_string(), score * 100));
+            sprints.insert(entry(&score.to_string(), score * 1000));
+        }
+        assert_eq!(
+            names(&scores),
+            ["12", "11", "10", "9", "8", "7", "6", "5", "4", "3"]
+        );
+        assert_eq!(
+            names(&sprints),
+            ["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"]
+        );
+
+        // full tables take better scores only, ties going to the older entry
+        assert!(!scores.qualifies(300));
+        assert_eq!(scores.insert(entry("tie", 1000)), Some(3));
+        assert_eq!(scores.entries.len(), HighScores::MAX_ENTRIES);
+        assert!(!sprints.qualifies(11000));
+        assert_eq!(sprints.insert(entry("fast", 500)), Some(0));
+        assert!(!HighScores::new(Mode::Cheese).qualifies(0));
+
+        // and so are the ones read from a file, cut to the size of the table
+        let text: String = (1..=12)
+            .rev()
+            .map(|pieces| {
+                format!(
+                    "[[score]]\nname = \"{0}\"\npieces = {0}\nlevel = 0\nlines = 0\ndate = \"\"\n",
+                    pieces
+                )
+            })
+            .collect();
+        let cheese = HighScores::parse(Mode::Cheese, &text).unwrap();
+        assert_eq!(
+            names(&cheese),
+            ["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"]
+        );
+    }
+
+    #[test]
+    fn names_are_cleaned_up() {
+        assert_eq!(sanitize_name("  bob  "), "bob");
+        assert_eq!(sanitize_name("b\"o\\b\u{7}"), "bob");
+        assert_eq!(sanitize_name("a very long name indeed"), "a very long name");
+        assert_eq!(sanitize_name(" \t"), "anonymous");
+    }
+
+    #[test]
+    fn dates_follow_the_calendar() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951782400), "2000-02-29");
+        assert_eq!(date(951868800 - 1), "2000-02-29");
+        assert_eq!(date(951868800), "2000-03-01");
+        assert_eq!(date(1709164800), "2024-02-29");
+        assert_eq!(date(1709251200), "2024-03-01");
+        // no leap day in 2100
+        assert_eq!(date(4107456000 + 86400), "2100-03-01");
+    }
+}
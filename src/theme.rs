@@ -0,0 +1,469 @@
+// Color themes, and detection of the terminal background to pick a readable one
+
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::style::Color;
+
+use ttrys::Tetromino;
+
+use crate::RawModeGuard;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Palette {
+    // the original palette, meant for dark backgrounds
+    Classic,
+    // saturated, bright colors for dark backgrounds
+    HighContrastDark,
+    // deep colors that stand out on light backgrounds
+    HighContrastLight,
+    // soft colors for dark backgrounds
+    Pastel,
+    // shades of grey, for terminals with 8 colors where the others come out wrong
+    Monochrome,
+}
+
+impl Palette {
+    // "auto" is not a palette by itself, see Palette::detect()
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(Palette::Classic),
+            "high-contrast-dark" => Some(Palette::HighContrastDark),
+            "high-contrast-light" => Some(Palette::HighContrastLight),
+            "pastel" => Some(Palette::Pastel),
+            "monochrome" => Some(Palette::Monochrome),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Classic => "classic",
+            Palette::HighContrastDark => "high-contrast-dark",
+            Palette::HighContrastLight => "high-contrast-light",
+            Palette::Pastel => "pastel",
+            Palette::Monochrome => "monochrome",
+        }
+    }
+
+    // Keep the classic palette on dark terminals, and switch to a high contrast one on light
+    // terminals where the light colors (yellow, white) wash out.
+    pub fn detect() -> Self {
+        match detect_background() {
+            Some(Background::Light) => Palette::HighContrastLight,
+            _ => Palette::Classic,
+        }
+    }
+
+    pub fn tetro_color(self, tetro: Tetromino) -> Color {
+        match self {
+            Palette::Classic => match tetro {
+                Tetromino::I => Color::Cyan,
+                Tetromino::J => Color::Blue,
+                Tetromino::L => Color::AnsiValue(214),
+                Tetromino::O => Color::Yellow,
+                Tetromino::S => Color::Green,
+                Tetromino::T => Color::Magenta,
+                Tetromino::Z => Color::Red,
+            },
+            Palette::HighContrastDark => match tetro {
+                Tetromino::I => Color::AnsiValue(51),
+                Tetromino::J => Color::AnsiValue(27),
+                Tetromino::L => Color::AnsiValue(208),
+                Tetromino::O => Color::AnsiValue(226),
+                Tetromino::S => Color::AnsiValue(46),
+                Tetromino::T => Color::AnsiValue(201),
+                Tetromino::Z => Color::AnsiValue(196),
+            },
+            Palette::HighContrastLight => match tetro {
+                Tetromino::I => Color::AnsiValue(30),
+                Tetromino::J => Color::AnsiValue(19),
+                Tetromino::L => Color::AnsiValue(166),
+                Tetromino::O => Color::AnsiValue(136),
+                Tetromino::S => Color::AnsiValue(28),
+                Tetromino::T => Color::AnsiValue(90),
+                Tetromino::Z => Color::AnsiValue(124),
+            },
+            Palette::Pastel => match tetro {
+                Tetromino::I => Color::AnsiValue(117),
+                Tetromino::J => Color::AnsiValue(111),
+                Tetromino::L => Color::AnsiValue(216),
+                Tetromino::O => Color::AnsiValue(229),
+                Tetromino::S => Color::AnsiValue(151),
+                Tetromino::T => Color::AnsiValue(183),
+                Tetromino::Z => Color::AnsiValue(210),
+            },
+            Palette::Monochrome => Color::Grey,
+        }
+    }
+
+    // the colors of the palette on truecolor terminals, when it has its own
+    pub fn tetro_rgb(self, tetro: Tetromino) -> Option<Color> {
+        let (r, g, b) = match self {
+            Palette::Classic => match tetro {
+                Tetromino::I => (0, 220, 230),
+                Tetromino::J => (40, 90, 240),
+                Tetromino::L => (245, 160, 0),
+                Tetromino::O => (240, 225, 0),
+                Tetromino::S => (40, 210, 60),
+                Tetromino::T => (170, 40, 230),
+                Tetromino::Z => (235, 30, 40),
+            },
+            Palette::Pastel => match tetro {
+                Tetromino::I => (140, 210, 235),
+                Tetromino::J => (150, 170, 240),
+                Tetromino::L => (245, 180, 130),
+                Tetromino::O => (245, 230, 150),
+                Tetromino::S => (170, 225, 170),
+                Tetromino::T => (200, 170, 230),
+                Tetromino::Z => (240, 150, 150),
+            },
+            Palette::HighContrastDark | Palette::HighContrastLight | Palette::Monochrome => {
+                return None
+            }
+        };
+        Some(Color::Rgb { r, g, b })
+    }
+
+    // color of the rows about to be cleared
+    pub fn clear_color(self) -> Color {
+        match self {
+            Palette::Classic
+            | Palette::HighContrastDark
+            | Palette::Pastel
+            | Palette::Monochrome => Color::White,
+            Palette::HighContrastLight => Color::Black,
+        }
+    }
+
+    pub fn garbage_color(self) -> Color {
+        match self {
+            Palette::Pastel => Color::AnsiValue(245),
+            _ => Color::DarkGrey,
+        }
+    }
+
+    pub fn border_color(self) -> Color {
+        match self {
+            Palette::Classic | Palette::Monochrome => Color::Reset,
+            Palette::HighContrastDark => Color::White,
+            Palette::HighContrastLight => Color::Black,
+            Palette::Pastel => Color::AnsiValue(146),
+        }
+    }
+
+    // background of the free cells of the stack, the terminal's own with Reset
+    pub fn background_color(self) -> Color {
+        match self {
+            Palette::HighContrastDark => Color::Black,
+            Palette::HighContrastLight => Color::White,
+            Palette::Classic | Palette::Pastel | Palette::Monochrome => Color::Reset,
+        }
+    }
+}
+
+// Levels between two shifts of the board colors
+const LEVELS_PER_TINT: u32 = 3;
+// Hues the board colors go through as the levels go up, after those of the palette
+const LEVEL_HUES: [(u8, u8, u8); 5] = [
+    (60, 130, 255),
+    (60, 220, 90),
+    (255, 200, 40),
+    (200, 90, 255),
+    (40, 210, 230),
+];
+
+// The colors used by the renderer: a palette, with optional per tetromino overrides from the
+// configuration. On truecolor terminals the palettes having RGB colors use them, and the minos
+// are shaded; elsewhere the RGB colors are brought down to the 256 colors palette.
+//
+// As in the classic games, the board changes colors every few levels: the border takes a new
+// hue, and on truecolor terminals the stack background a faint tint of it.
+#[derive(Clone, Debug)]
+pub struct Theme {
+    palette: Palette,
+    piece_colors: [Option<Color>; 7],
+    truecolor: bool,
+    level: u32,
+}
+
+impl Theme {
+    pub fn new(palette: Palette, piece_colors: [Option<Color>; 7]) -> Self {
+        Theme {
+            palette,
+            piece_colors,
+            truecolor: detect_truecolor(),
+            level: 0,
+        }
+    }
+
+    // The level of the game drawn, for the board colors
+    pub fn set_level(&mut self, level: u32) {
+        self.level = level;
+    }
+
+    // The hue of the board at the current level, None while it keeps the colors of the palette.
+    // The shades of grey of the monochrome palette stay as they are.
+    fn level_hue(&self) -> Option<(u8, u8, u8)> {
+        if self.palette == Palette::Monochrome {
+            return None;
+        }
+        let shift = (self.level / LEVELS_PER_TINT) as usize % (LEVEL_HUES.len() + 1);
+        shift.checked_sub(1).map(|index| LEVEL_HUES[index])
+    }
+
+    // The terminal's own color on truecolor terminals, the closest of the 256 colors elsewhere
+    fn fit(&self, color: Color) -> Color {
+        if self.truecolor {
+            color
+        } else {
+            downgrade(color)
+        }
+    }
+
+    pub fn tetro_color(&self, tetro: Tetromino) -> Color {
+        let color = self.piece_colors[tetro as usize]
+            .or_else(|| self.palette.tetro_rgb(tetro).filter(|_| self.truecolor))
+            .unwrap_or_else(|| self.palette.tetro_color(tetro));
+        self.fit(color)
+    }
+
+    // color of the outline of the ghost piece, apart from the pieces when they all look alike, and
+    // tinted toward grey with true colors
+    pub fn ghost_color(&self, tetro: Tetromino) -> Color {
+        match self.palette {
+            Palette::Monochrome if self.piece_colors[tetro as usize].is_none() => Color::White,
+            _ if self.truecolor => mix(self.tetro_color(tetro), (128, 128, 128), 0.4),
+            _ => self.tetro_color(tetro),
+        }
+    }
+
+    // A darker tone of a mino color for the right half of the minos, None without true colors
+    pub fn shade(&self, color: Color) -> Option<Color> {
+        self.truecolor.then(|| mix(color, (0, 0, 0), 0.2))
+    }
+
+    pub fn clear_color(&self) -> Color {
+        self.palette.clear_color()
+    }
+
+    // color of the garbage rows, apart from the pieces
+    pub fn garbage_color(&self) -> Color {
+        self.palette.garbage_color()
+    }
+
+    // darker on the light palette, to stand out from the background
+    pub fn border_color(&self) -> Color {
+        match self.level_hue() {
+            Some((r, g, b)) if self.palette == Palette::HighContrastLight => {
+                self.fit(mix(Color::Rgb { r, g, b }, (0, 0, 0), 0.4))
+            }
+            Some((r, g, b)) => self.fit(Color::Rgb { r, g, b }),
+            None => self.palette.border_color(),
+        }
+    }
+
+    pub fn background_color(&self) -> Color {
+        match self.level_hue() {
+            Some((r, g, b)) if self.truecolor => {
+                let base = match self.palette {
+                    Palette::HighContrastLight => (255, 255, 255),
+                    _ => (0, 0, 0),
+                };
+                mix(Color::Rgb { r, g, b }, base, 0.88)
+            }
+            _ => self.palette.background_color(),
+        }
+    }
+}
+
+// Whether the terminal takes 24 bits colors, as it tells through COLORTERM
+fn detect_truecolor() -> bool {
+    std::env::var("COLORTERM").is_ok_and(|value| value == "truecolor" || value == "24bit")
+}
+
+// RGB value of a color, as xterm draws the indexed ones
+fn to_rgb(color: Color) -> Option<(u8, u8, u8)> {
+    const ANSI: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    let index = match color {
+        Color::Rgb { r, g, b } => return Some((r, g, b)),
+        Color::AnsiValue(index) => index,
+        Color::Black => 0,
+        Color::DarkRed => 1,
+        Color::DarkGreen => 2,
+        Color::DarkYellow => 3,
+        Color::DarkBlue => 4,
+        Color::DarkMagenta => 5,
+        Color::DarkCyan => 6,
+        Color::Grey => 7,
+        Color::DarkGrey => 8,
+        Color::Red => 9,
+        Color::Green => 10,
+        Color::Yellow => 11,
+        Color::Blue => 12,
+        Color::Magenta => 13,
+        Color::Cyan => 14,
+        Color::White => 15,
+        Color::Reset => return None,
+    };
+    Some(match index {
+        0..=15 => ANSI[index as usize],
+        16..=231 => {
+            let level = |value: u8| CUBE_LEVELS[value as usize];
+            let index = index - 16;
+            (level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        _ => {
+            let grey = 8 + 10 * (index - 232);
+            (grey, grey, grey)
+        }
+    })
+}
+
+// levels of each channel in the 6x6x6 color cube of the 256 colors palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// The closest color of the 256 colors palette to an RGB one, the others are left as they are
+fn downgrade(color: Color) -> Color {
+    let Color::Rgb { r, g, b } = color else {
+        return color;
+    };
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        let channel = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        channel(r, r2) + channel(g, g2) + channel(b, b2)
+    };
+    let nearest_level = |value: u8| {
+        (0..6)
+            .min_by_key(|&level| (CUBE_LEVELS[level] as i32 - value as i32).abs())
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * nearest_level(r) + 6 * nearest_level(g) + nearest_level(b);
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let grey = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+    [cube, grey]
+        .into_iter()
+        .min_by_key(|&index| to_rgb(Color::AnsiValue(index)).map_or(i32::MAX, distance))
+        .map_or(color, Color::AnsiValue)
+}
+
+// A color moved toward another one by `amount` (0 to 1), as an RGB value
+fn mix(color: Color, (r2, g2, b2): (u8, u8, u8), amount: f32) -> Color {
+    let Some((r, g, b)) = to_rgb(color) else {
+        return color;
+    };
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    Color::Rgb {
+        r: channel(r, r2),
+        g: channel(g, g2),
+        b: channel(b, b2),
+    }
+}
+
+// Parse a color given by name ("dark_blue"), 256 colors index ("208") or RGB value ("#ff8700")
+pub fn parse_color(value: &str) -> Option<Color> {
+    if let Ok(color) = Color::try_from(value) {
+        Some(color)
+    } else if let Ok(index) = value.parse() {
+        Some(Color::AnsiValue(index))
+    } else {
+        let hex = value.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+        let channel = |idx: usize| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok();
+        Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Background {
+    Dark,
+    Light,
+}
+
+fn detect_background() -> Option<Background> {
+    query_background().or_else(background_from_env)
+}
+
+// Ask the terminal for its background color with an OSC 11 query. Terminals that support it
+// answer with "ESC ] 11 ; rgb:RRRR/GGGG/BBBB" terminated by BEL or ST; the others stay silent,
+// hence the short timeout.
+fn query_background() -> Option<Background> {
+    use crossterm::event::{poll, read, Event, KeyCode, KeyModifiers};
+
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return None;
+    }
+
+    let _raw_mode = RawModeGuard::new();
+    let mut out = std::io::stdout();
+    out.write_all(b"\x1b]11;?\x07").ok()?;
+    out.flush().ok()?;
+
+    // crossterm does not know about OSC replies and decodes them as a series of key events,
+    // which are reassembled here.
+    let mut reply = String::new();
+    let deadline = Instant::now() + TIMEOUT;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        if !poll(remaining).ok()? {
+            break;
+        }
+        if let Event::Key(key) = read().ok()? {
+            match key.code {
+                // BEL
+                KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                // ST (ESC \)
+                KeyCode::Char('\\') if key.modifiers.contains(KeyModifiers::ALT) => break,
+                KeyCode::Char(c) => reply.push(c),
+                _ => (),
+            }
+        }
+    }
+
+    let rgb = reply.split("rgb:").nth(1)?;
+    let mut channels = rgb.split('/').map(|channel| {
+        let digits = channel.get(..channel.len().min(4))?;
+        let value = u32::from_str_radix(digits, 16).ok()?;
+        let max = (1u32 << (4 * digits.len())) - 1;
+        Some(value as f32 / max as f32)
+    });
+    let (r, g, b) = (channels.next()??, channels.next()??, channels.next()??);
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    Some(if luminance > 0.5 {
+        Background::Light
+    } else {
+        Background::Dark
+    })
+}
+
+// Some terminals (rxvt, Konsole, ...) export their colors as "fg;bg" indices in the 16 colors
+// ANSI palette.
+fn background_from_env() -> Option<Background> {
+    let value = std::env::var("COLORFGBG").ok()?;
+    let background: u8 = value.rsplit(';').next()?.parse().ok()?;
+    Some(if matches!(background, 7 | 9..=15) {
+        Background::Light
+    } else {
+        Background::Dark
+    })
+}
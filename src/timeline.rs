@@ -0,0 +1,123 @@
+// Session timeline: the score, stack height and placement speed sampled every second of play,
+// shown as sparklines on the results screen.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use ttrys::{State, Ttrys};
+
+struct Sample {
+    score: u32,
+    stack_height: i8,
+    // pieces per second, averaged over the last PPS_WINDOW samples
+    pps: f32,
+}
+
+pub struct Timeline {
+    samples: Vec<Sample>,
+    // playing time, pauses excluded
+    played: Duration,
+    last_update: Instant,
+    // piece count at each of the recent samples
+    recent_pieces: VecDeque<u32>,
+}
+
+impl Timeline {
+    const SAMPLE_PERIOD: Duration = Duration::from_secs(1);
+    const PPS_WINDOW: usize = 5;
+    // number of columns of the sparklines, longer games are downsampled
+    const WIDTH: usize = 60;
+
+    pub fn new() -> Self {
+        Timeline {
+            samples: Vec::new(),
+            played: Duration::ZERO,
+            last_update: Instant::now(),
+            recent_pieces: VecDeque::with_capacity(Self::PPS_WINDOW + 1),
+        }
+    }
+
+    // To be called regularly during the game, takes a sample when due
+    pub fn update(&mut self, ttrys: &Ttrys) {
+        let now = Instant::now();
+        if !matches!(ttrys.state(), State::Paused | State::Countdown(_)) {
+            self.played += now - self.last_update;
+        }
+        self.last_update = now;
+
+        while self.played >= Self::SAMPLE_PERIOD * (self.samples.len() as u32 + 1) {
+            let pieces = ttrys.stats().pieces();
+            if self.recent_pieces.len() > Self::PPS_WINDOW {
+                self.recent_pieces.pop_front();
+            }
+            self.recent_pieces.push_back(pieces);
+            let window = self.recent_pieces.len() - 1;
+            let pps = match window {
+                0 => pieces as f32,
+                _ => (pieces - self.recent_pieces[0]) as f32 / window as f32,
+            };
+            self.samples.push(Sample {
+                score: ttrys.score().1,
+                stack_height: ttrys.stack_height() + 1,
+                pps,
+            });
+        }
+    }
+
+    pub fn print(&self) {
+        if self.samples.len() < 2 {
+            return;
+        }
+        let series = |value: &dyn Fn(&Sample) -> f32| -> Vec<f32> {
+            self.samples.iter().map(value).collect()
+        };
+        let score = series(&|sample| sample.score as f32);
+        let height = series(&|sample| sample.stack_height as f32);
+        let pps = series(&|sample| sample.pps);
+        let peak = |values: &[f32]| values.iter().copied().fold(0.0, f32::max);
+
+        println!(
+            "Timeline ({}s, one column is {:.0}s):\x1b[0K",
+            self.samples.len(),
+            (self.samples.len() as f32 / Self::WIDTH as f32).max(1.0)
+        );
+        println!("  Score   {}  {}\x1b[0K", sparkline(&score), peak(&score));
+        println!(
+            "  Height  {}  max {}\x1b[0K",
+            sparkline(&height),
+            peak(&height)
+        );
+        println!(
+            "  PPS     {}  max {:.1}\x1b[0K",
+            sparkline(&pps),
+            peak(&pps)
+        );
+    }
+}
+
+// Render values as a line of block characters, scaled between 0 and the maximum value
+fn sparkline(values: &[f32]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    // average the values falling in each column
+    let columns = values.len().min(Timeline::WIDTH);
+    let resampled: Vec<f32> = (0..columns)
+        .map(|col| {
+            let start = col * values.len() / columns;
+            let end = ((col + 1) * values.len() / columns).max(start + 1);
+            values[start..end].iter().sum::<f32>() / (end - start) as f32
+        })
+        .collect();
+
+    let max = resampled.iter().copied().fold(0.0, f32::max);
+    resampled
+        .iter()
+        .map(|&value| {
+            if max > 0.0 {
+                BARS[((value / max) * (BARS.len() - 1) as f32).round() as usize]
+            } else {
+                BARS[0]
+            }
+        })
+        .collect()
+}
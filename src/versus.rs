@@ -0,0 +1,172 @@
+// Local versus: two games side by side in the same terminal, the players sharing the keyboard. The
+// lines cleared by one player are sent to the other as garbage rows, the first to top out loses.
+//
+// Both games deal the same pieces. The left player moves with WASD, hard drops with space and
+// holds with E; the right player uses the arrows, Enter and '/'. P pauses both games and Esc ends
+// the match.
+
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{Event, KeyCode, KeyModifiers};
+
+use ttrys::rotation;
+use ttrys::{GameEvent, Options, State, Ttrys, UserAction};
+
+use crate::{spawn_input, GameScreen, KeyMap, Layout, TerminalGuard, Timeout};
+
+pub fn run(
+    options: Options,
+    rules: &str,
+    floor_slide: bool,
+    mut displays: [GameScreen; 2],
+    frame_interval: Duration,
+) {
+    let keymaps = [
+        KeyMap::new(Layout::VersusLeft),
+        KeyMap::new(Layout::VersusRight),
+    ];
+    let mut wins = [0; 2];
+    let terminal = TerminalGuard::new();
+    let input = spawn_input();
+    'session: loop {
+        // a fresh seed for each match, shared by both players
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let mut games = [(); 2].map(|_| {
+            let mut ttrys = Ttrys::new(Options {
+                seed: Some(seed),
+                ..options.clone()
+            });
+            ttrys.set_floor_slide(floor_slide);
+            if let Some(rotation_system) = rotation::from_preset(rules) {
+                ttrys.set_rotation_system(rotation_system);
+            }
+            ttrys
+        });
+
+        // each game runs on its own gravity timer, the screen is redrawn as in a single game
+        let mut timeouts = [(); 2].map(|_| Timeout::new(Duration::default()));
+        let mut last_draw: Option<Instant> = None;
+        let mut dirty = true;
+        while games.iter().all(Ttrys::running) {
+            let now = Instant::now();
+            let frame_due = last_draw.map_or(now, |last_draw| last_draw + frame_interval);
+            let animated = displays.iter().any(GameScreen::animated);
+            if (dirty || animated) && now >= frame_due {
+                draw(&mut displays, &games, None).ok();
+                last_draw = Some(now);
+                dirty = false;
+            }
+
+            let mut wait = timeouts
+                .iter()
+                .map(Timeout::remaining)
+                .min()
+                .unwrap_or_default();
+            if dirty || animated {
+                wait = wait.min(frame_due.saturating_duration_since(now));
+            }
+            match input.recv_timeout(wait) {
+                Ok((_, Event::Key(key))) => {
+                    let binding = keymaps
+                        .iter()
+                        .enumerate()
+                        .find_map(|(player, keymap)| Some((player, keymap.action(key)?)));
+                    if let Some((player, action)) = binding {
+                        // pausing and quitting are for both players
+                        let players = match action {
+                            UserAction::TogglePause | UserAction::Quit => 0..2,
+                            _ => player..player + 1,
+                        };
+                        for player in players {
+                            games[player].update(action);
+                            displays[player].add_events(games[player].events());
+                            if games[player].state() == State::Paused {
+                                timeouts[player].pause();
+                            } else {
+                                timeouts[player].resume();
+                            }
+                        }
+                        if action == UserAction::Quit {
+                            break 'session;
+                        }
+                        dirty = true;
+                    }
+                }
+                Ok(_) => dirty = true,
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break 'session,
+            }
+
+            for player in 0..2 {
+                if timeouts[player].expired() {
+                    games[player].step();
+                    displays[player].add_events(games[player].events());
+                    let cleared = games[player].events().iter().find_map(|event| match event {
+                        GameEvent::LinesCleared { rows, .. } => Some(*rows),
+                        _ => None,
+                    });
+                    if let Some(rows) = cleared {
+                        games[1 - player].add_garbage(Ttrys::garbage_for(rows));
+                    }
+                    timeouts[player] = Timeout::new(games[player].step_duration());
+                    dirty = true;
+                }
+            }
+        }
+
+        // the player still standing wins, both topping out at once is a draw
+        let winner = games.iter().position(Ttrys::running);
+        if let Some(winner) = winner {
+            wins[winner] += 1;
+        }
+        let panels = [0, 1].map(|player| {
+            vec![
+                String::from(match winner {
+                    Some(winner) if winner == player => "YOU WIN",
+                    Some(_) => "GAME OVER",
+                    None => "DRAW",
+                }),
+                String::new(),
+                format!("Wins {}", wins[player]),
+                format!("Lines {}", games[player].stats().lines()),
+                String::new(),
+                String::from("[R] rematch"),
+                String::from("[Q] quit"),
+            ]
+        });
+        loop {
+            draw(&mut displays, &games, Some(&panels)).ok();
+            match input.recv() {
+                Ok((_, Event::Key(key))) => match key.code {
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        break 'session
+                    }
+                    KeyCode::Char('r' | 'R') => break,
+                    KeyCode::Char('q' | 'Q') | KeyCode::Esc => break 'session,
+                    _ => (),
+                },
+                Ok(_) => (),
+                Err(_) => break 'session,
+            }
+        }
+    }
+    drop(terminal);
+    println!("Versus: {} - {}", wins[0], wins[1]);
+}
+
+// Draw both boards in one frame, with a panel over each if given
+fn draw(
+    displays: &mut [GameScreen; 2],
+    games: &[Ttrys; 2],
+    panels: Option<&[Vec<String>; 2]>,
+) -> crossterm::Result<()> {
+    let mut screen = displays[0].begin_frame()?;
+    for (player, display) in displays.iter_mut().enumerate() {
+        display.render(screen.canvas(), &games[player]);
+        if let Some(panels) = panels {
+            display.render_panel(screen.canvas(), &games[player], &panels[player]);
+        }
+    }
+    displays[0].end_frame(screen)
+}